@@ -0,0 +1,139 @@
+use crate::line::Line;
+
+// 两个文档之间一行的变化
+#[derive(Clone, Copy)]
+pub enum Change<'a> {
+    Same(&'a Line), // 两边都有，取新文档中的行
+    Added(&'a Line), // 只在新文档中
+    Removed(&'a Line), // 只在旧文档中
+}
+
+// 对比两个文档的行，缩进和内容都相同才算同一行，结果按新文档的顺序排列，
+// 被删除的行出现在它原来所在的位置
+pub fn diff<'a>(old: &'a [Line], new: &'a [Line]) -> Vec<Change<'a>> {
+    // 先去掉相同的开头和结尾，缩小后面计算的规模
+    let prefix = old.iter().zip(new).take_while(|(a, b)| same(a, b)).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| same(a, b))
+        .count();
+
+    let mut changes: Vec<Change> = new[..prefix].iter().map(Change::Same).collect();
+    align(&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix], &mut changes);
+    changes.extend(new[new.len() - suffix..].iter().map(Change::Same));
+
+    changes
+}
+
+// 缩进和内容都相同才算同一行
+fn same(a: &Line, b: &Line) -> bool {
+    a.indent == b.indent && a.content == b.content
+}
+
+// 按最长公共子序列对齐两段行，用 Hirschberg 的方法只需要线性的内存：
+// 把旧的一段从中间分开，找到新的一段中使两半的公共子序列之和最长的位置，再分别对齐两半
+fn align<'a>(old: &'a [Line], new: &'a [Line], changes: &mut Vec<Change<'a>>) {
+    match old {
+        [] => changes.extend(new.iter().map(Change::Added)),
+        _ if new.is_empty() => changes.extend(old.iter().map(Change::Removed)),
+        [line] => match new.iter().position(|other| same(line, other)) {
+            Some(j) => {
+                changes.extend(new[..j].iter().map(Change::Added));
+                changes.push(Change::Same(&new[j]));
+                changes.extend(new[j + 1..].iter().map(Change::Added));
+            },
+            None => {
+                changes.push(Change::Removed(line));
+                changes.extend(new.iter().map(Change::Added));
+            },
+        },
+        _ => {
+            let middle = old.len() / 2;
+            let left = lcs_row(old[..middle].iter(), new.iter());
+            let right = lcs_row(old[middle..].iter().rev(), new.iter().rev());
+
+            // left[j] 对应 new[..j]，right[m - j] 对应 new[j..]
+            let m = new.len();
+            let split = (0..=m).max_by_key(|&j| (left[j] + right[m - j], std::cmp::Reverse(j))).unwrap();
+
+            align(&old[..middle], &new[..split], changes);
+            align(&old[middle..], &new[split..], changes);
+        },
+    }
+}
+
+// 最长公共子序列长度表的最后一行，row[j] 是 old 和 new 的前 j 行的结果
+fn lcs_row<'a>(old: impl Iterator<Item = &'a Line>, new: impl Iterator<Item = &'a Line> + Clone) -> Vec<usize> {
+    let m = new.clone().count();
+    let mut row = vec![0; m + 1];
+
+    for a in old {
+        // row[j] 更新前是上一行的值，diagonal 是上一行的 row[j - 1]
+        let mut diagonal = 0;
+        for (j, b) in new.clone().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = match same(a, b) {
+                true => diagonal + 1,
+                false => above.max(row[j]),
+            };
+            diagonal = above;
+        }
+    }
+
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_lore::parse_with_options;
+    use crate::parser::ParseOptions;
+
+    fn parse(input: &str) -> Vec<Line> {
+        parse_with_options(input, &ParseOptions::default())
+    }
+
+    // 把变化还原成旧文档和新文档
+    fn sides(changes: &[Change]) -> (Vec<String>, Vec<String>) {
+        let (mut old, mut new) = (Vec::new(), Vec::new());
+        for change in changes {
+            match change {
+                Change::Same(line) => {
+                    old.push(line.to_string());
+                    new.push(line.to_string());
+                },
+                Change::Removed(line) => old.push(line.to_string()),
+                Change::Added(line) => new.push(line.to_string()),
+            }
+        }
+        (old, new)
+    }
+
+    fn texts(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn changes_rebuild_both_documents_with_the_longest_common_part() {
+        let old = parse("a\nb\nc\nd\ne\nf\ng");
+        let new = parse("x\nb\nd\nc\ne\ny\ng\nz");
+        let changes = diff(&old, &new);
+
+        assert_eq!(sides(&changes), (texts(&old), texts(&new)));
+        assert_eq!(changes.iter().filter(|change| matches!(change, Change::Same(_))).count(), 4);
+    }
+
+    #[test]
+    fn large_documents_that_differ_at_both_ends() {
+        let body: String = (0..1_000).map(|i| format!("+ d{}\n  x{} = https://example.com/{}\n", i, i, i)).collect();
+        let old = parse(&format!("old first\n{}old last", body));
+        let new = parse(&format!("new first\n{}new last", body));
+        let changes = diff(&old, &new);
+
+        assert_eq!(changes.len(), 2_004);
+        assert_eq!(sides(&changes), (texts(&old), texts(&new)));
+        assert!(matches!(changes[0], Change::Removed(_)) && matches!(changes[1], Change::Added(_)));
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+// 解析错误的种类，调用方可以据此区分空文档和写法错误，不需要比较 message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    Syntax, // 某一行的写法不合法
+    Empty, // 不允许空文档时遇到了没有任何内容的文档
+    DanglingReference, // 引用的目标不是文档中的 domain
+    CyclicReference, // 引用指向包含它的 domain，展开时会无限循环
+}
+
+// 解析错误，记录出错的行号和列号（均从 1 开始）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    pub kind: ParseErrorKind,
+}
+
+impl ParseError {
+    // 写法错误
+    pub fn new(line: usize, column: usize, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line,
+            column,
+            message: message.into(),
+            kind: ParseErrorKind::Syntax,
+        }
+    }
+
+    // 空文档，位置总是第一行第一列
+    pub fn empty() -> ParseError {
+        ParseError {
+            kind: ParseErrorKind::Empty,
+            ..ParseError::new(1, 1, "document is empty")
+        }
+    }
+
+    // 生成类似 rustc 的源码片段，在出错的列下方标出 ^
+    pub fn render_with_source(&self, source: &str) -> String {
+        // 取出出错的那一行
+        let text = source
+            .split('\n')
+            .nth(self.line.saturating_sub(1))
+            .unwrap_or("")
+            .trim_end_matches('\r');
+
+        // 行号栏的宽度
+        let gutter = " ".repeat(self.line.to_string().len());
+
+        // ^ 之前的空白，与出错位置之前的文字等宽，制表符原样保留
+        let offset: String = text
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| match c {
+                '\t' => "\t".to_string(),
+                c => " ".repeat(crate::root::display_width(c.encode_utf8(&mut [0; 4]))),
+            })
+            .collect();
+
+        format!(
+            "error: {}\n{}--> {}:{}\n{} |\n{} | {}\n{} | {}^",
+            self.message,
+            gutter, self.line, self.column,
+            gutter,
+            self.line, text,
+            gutter, offset
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// 诊断的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+// 检查文档时得到的一条诊断，code 对每种问题保持稳定，方便 CI 解析
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+}
+
+// 重新解析输出的文本后与原文档不一致
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundtripError {
+    pub line: usize, // 原文档中第一个不一致的行号
+    pub expected: String, // 种类和文字，比如 atom `+ bullet`，没有对应的行时为 nothing
+    pub found: String,
+}
+
+impl fmt::Display for RoundtripError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} does not roundtrip: expected {}, found {}", self.line, self.expected, self.found)
+    }
+}
+
+impl std::error::Error for RoundtripError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::check_line;
+
+    #[test]
+    fn caret_points_at_odd_indentation() {
+        let source = "+ links\n   bai_du = https://www.baidu.com";
+        let error = check_line(2, source.split('\n').nth(1).unwrap()).unwrap_err();
+
+        assert_eq!(error.column, 4);
+        assert_eq!(
+            error.render_with_source(source),
+            "error: indentation is not a multiple of 2 spaces\n --> 2:4\n  |\n2 |    bai_du = https://www.baidu.com\n  |    ^"
+        );
+    }
+
+    #[test]
+    fn caret_points_at_separator() {
+        let source = "bai_du =";
+        let error = check_line(1, source).unwrap_err();
+
+        assert_eq!(error.column, 8);
+        assert!(error.render_with_source(source).ends_with("1 | bai_du =\n  |        ^"));
+    }
+
+    #[test]
+    fn caret_counts_display_width() {
+        let source = "中文 =";
+        let error = check_line(1, source).unwrap_err();
+
+        // 两个汉字各占两格
+        assert_eq!(error.column, 4);
+        assert!(error.render_with_source(source).ends_with("1 | 中文 =\n  |      ^"));
+    }
+
+    #[test]
+    fn caret_keeps_tabs() {
+        let source = "\t\tkey =";
+        let error = check_line(1, source).unwrap_err();
+
+        assert!(error.render_with_source(source).ends_with("1 | \t\tkey =\n  | \t\t    ^"));
+    }
+}
@@ -0,0 +1,431 @@
+use crate::json;
+use crate::line::{Content, Line};
+use crate::root::Root;
+use crate::tree;
+
+// 导出为 OPML 2.0，domain 是包含子节点的 outline，link 带有 htmlUrl，outline 按文档顺序排列
+pub fn to_opml(lines: &[Line], title: &str) -> String {
+    let mut opml = String::new();
+
+    opml.push_str(&format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+  <head>
+    <title>{}</title>
+  </head>
+  <body>
+"#,
+        escape_xml(title)
+    ));
+
+    // 当前打开的 outline 的缩进
+    let mut open: Vec<usize> = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        // 关闭缩进不浅于当前行的 outline
+        while open.last().is_some_and(|&indent| indent >= line.indent) {
+            open.pop();
+            opml.push_str(&format!("{}</outline>\n", "  ".repeat(open.len() + 2)));
+        }
+
+        let attributes = match &line.content {
+            Content::Atom(atom) => format!(r#"text="{}""#, escape_xml(atom.trim())),
+            Content::Link(key, value) => format!(
+                r#"text="{}" htmlUrl="{}""#,
+                escape_xml(key),
+                escape_xml(value)
+            ),
+            Content::Domain(domain) => format!(r#"text="{}""#, escape_xml(domain)),
+            Content::Reference(name, target) => format!(r#"text="{} &gt; {}""#, escape_xml(name), escape_xml(target)),
+            Content::Elided(count) => format!(r#"text="… ({} more)""#, count),
+        };
+
+        // 有子节点的行需要展开
+        let has_children = lines.get(index + 1).is_some_and(|next| next.indent > line.indent);
+
+        let padding = "  ".repeat(open.len() + 2);
+        if has_children {
+            opml.push_str(&format!("{}<outline {}>\n", padding, attributes));
+            open.push(line.indent);
+        } else {
+            opml.push_str(&format!("{}<outline {}/>\n", padding, attributes));
+        }
+    }
+
+    while open.pop().is_some() {
+        opml.push_str(&format!("{}</outline>\n", "  ".repeat(open.len() + 2)));
+    }
+
+    opml.push_str("  </body>\n</opml>\n");
+
+    opml
+}
+
+// 导出为 JSON Lines，按文档顺序每个节点一行，parent_index 是父节点所在的行（从 0 开始），
+// link 和引用另外带有 value，即链接或引用的目标
+pub fn to_jsonl(root: &Root) -> String {
+    let parents = tree::parents(&root.lines);
+    let mut depths: Vec<usize> = Vec::with_capacity(root.lines.len());
+    let mut jsonl = String::new();
+
+    for (line, parent) in root.lines.iter().zip(&parents) {
+        let depth = parent.map_or(0, |parent| depths[parent] + 1);
+        depths.push(depth);
+
+        let (text, value) = text_and_value(&line.content);
+
+        jsonl.push_str(&format!(
+            r#"{{"depth":{},"kind":{},"text":{},"parent_index":{}"#,
+            depth,
+            json::quote(line.content.kind_name()),
+            json::quote(&text),
+            parent.map_or("null".to_string(), |parent| parent.to_string())
+        ));
+        if let Some(value) = value {
+            jsonl.push_str(&format!(r#","value":{}"#, json::quote(value)));
+        }
+        jsonl.push_str("}\n");
+    }
+
+    jsonl
+}
+
+// 导出为嵌套的 json 数组，每个节点有 kind 和 text，link 和引用另外有 value，有子节点时带有 children，
+// 兄弟节点按文档顺序排列
+pub fn to_json(root: &Root) -> String {
+    let mut children: Vec<Vec<usize>> = (0..root.lines.len()).map(|_| Vec::new()).collect();
+    let mut roots = Vec::new();
+
+    for (index, parent) in tree::parents(&root.lines).into_iter().enumerate() {
+        match parent {
+            Some(parent) => children[parent].push(index),
+            None => roots.push(index),
+        }
+    }
+
+    nodes_json(&root.lines, &children, &roots)
+}
+
+// 一组兄弟节点的 json 数组
+fn nodes_json(lines: &[Line], children: &[Vec<usize>], indices: &[usize]) -> String {
+    let nodes: Vec<String> = indices
+        .iter()
+        .map(|&index| {
+            let line = &lines[index];
+            let (text, value) = text_and_value(&line.content);
+
+            let mut node = format!(r#"{{"kind":{},"text":{}"#, json::quote(line.content.kind_name()), json::quote(&text));
+            if let Some(value) = value {
+                node.push_str(&format!(r#","value":{}"#, json::quote(value)));
+            }
+            if !children[index].is_empty() {
+                node.push_str(&format!(r#","children":{}"#, nodes_json(lines, children, &children[index])));
+            }
+            node.push('}');
+
+            node
+        })
+        .collect();
+
+    format!("[{}]", nodes.join(","))
+}
+
+// 节点在 json 中的 text 和 value
+fn text_and_value(content: &Content) -> (String, Option<&str>) {
+    match content {
+        Content::Atom(atom) => (atom.trim().to_string(), None),
+        Content::Link(key, value) => (key.clone(), Some(value)),
+        Content::Domain(domain) => (domain.clone(), None),
+        Content::Reference(name, target) => (name.clone(), Some(target)),
+        Content::Elided(count) => (format!("… ({} more)", count), None),
+    }
+}
+
+// 纯文本大纲中每一项的开头
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Bullet {
+    #[default]
+    Dash, // `- `
+    Star, // `* `
+    Numbered, // `1. `，每一级单独编号
+}
+
+// 导出为便于阅读的纯文本大纲，按文档顺序每个节点一行，link 显示为 `name (url)`，不能再解析回 lore
+pub fn to_outline(root: &Root, bullet: Bullet) -> String {
+    let mut outline = String::new();
+
+    // 当前路径上各节点的缩进，以及每一级已经编到的序号
+    let mut stack: Vec<usize> = Vec::new();
+    let mut numbers: Vec<usize> = Vec::new();
+
+    for line in &root.lines {
+        while stack.last().is_some_and(|&indent| indent >= line.indent) {
+            stack.pop();
+        }
+        let depth = stack.len();
+        stack.push(line.indent);
+
+        // 回到浅的一级时，更深的编号重新开始
+        numbers.truncate(depth + 1);
+        if numbers.len() == depth {
+            numbers.push(0);
+        }
+        numbers[depth] += 1;
+
+        let marker = match bullet {
+            Bullet::Dash => "- ".to_string(),
+            Bullet::Star => "* ".to_string(),
+            Bullet::Numbered => format!("{}. ", numbers[depth]),
+        };
+
+        let text = match &line.content {
+            Content::Atom(atom) => atom.trim().to_string(),
+            Content::Link(key, value) => format!("{} ({})", key, value),
+            Content::Domain(domain) => domain.to_string(),
+            Content::Reference(name, target) => format!("{} → {}", name, target),
+            Content::Elided(count) => format!("… ({} more)", count),
+        };
+
+        outline.push_str(&format!("{}{}{}\n", "  ".repeat(depth), marker, text));
+    }
+
+    outline
+}
+
+// 转义 xml 属性和文本
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// 文档的结构描述：有哪些 domain，以及每个 domain 下出现过哪些种类的子节点
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schema {
+    pub top_level: Vec<&'static str>, // 顶层出现过的种类
+    pub domains: Vec<DomainShape>,
+}
+
+// 一个 domain 的结构，同一路径的 domain 出现多次时合并
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainShape {
+    pub path: Vec<String>,
+    pub child_kinds: Vec<&'static str>,
+}
+
+impl Schema {
+    pub fn to_json(&self) -> String {
+        let kinds = |kinds: &[&str]| {
+            kinds.iter().map(|kind| json::quote(kind)).collect::<Vec<_>>().join(",")
+        };
+
+        let domains: Vec<String> = self
+            .domains
+            .iter()
+            .map(|domain| {
+                let path: Vec<String> = domain.path.iter().map(|name| json::quote(name)).collect();
+                format!(r#"{{"path":[{}],"child_kinds":[{}]}}"#, path.join(","), kinds(&domain.child_kinds))
+            })
+            .collect();
+
+        format!(r#"{{"top_level":[{}],"domains":[{}]}}"#, kinds(&self.top_level), domains.join(","))
+    }
+}
+
+// 生成文档的结构描述，只关心结构，不包含内容
+pub fn describe(root: &Root) -> Schema {
+    let mut schema = Schema {
+        top_level: Vec::new(),
+        domains: Vec::new(),
+    };
+
+    // 祖先的缩进，以及是 domain 时它在 schema.domains 中的位置
+    let mut stack: Vec<(usize, Option<usize>)> = Vec::new();
+
+    for line in &root.lines {
+        while stack.last().is_some_and(|&(indent, _)| indent >= line.indent) {
+            stack.pop();
+        }
+
+        let kind = line.content.kind_name();
+
+        // 记录到父节点上，父节点不是 domain 时不记录
+        match stack.last() {
+            None => push_kind(&mut schema.top_level, kind),
+            Some(&(_, Some(domain))) => push_kind(&mut schema.domains[domain].child_kinds, kind),
+            Some(&(_, None)) => {},
+        }
+
+        let domain = match &line.content {
+            Content::Domain(name) => {
+                // 路径是最近的祖先 domain 的路径再加上自己
+                let mut path = stack
+                    .iter()
+                    .rev()
+                    .find_map(|&(_, domain)| domain)
+                    .map_or_else(Vec::new, |domain| schema.domains[domain].path.clone());
+                path.push(name.clone());
+
+                match schema.domains.iter().position(|shape| shape.path == path) {
+                    Some(position) => Some(position),
+                    None => {
+                        schema.domains.push(DomainShape {
+                            path,
+                            child_kinds: Vec::new(),
+                        });
+                        Some(schema.domains.len() - 1)
+                    },
+                }
+            },
+            _ => None,
+        };
+
+        stack.push((line.indent, domain));
+    }
+
+    schema
+}
+
+// 记录一种子节点，不重复
+fn push_kind(kinds: &mut Vec<&'static str>, kind: &'static str) {
+    if !kinds.contains(&kind) {
+        kinds.push(kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_lore::{parse_root, parse_with_options};
+    use crate::parser::ParseOptions;
+
+    fn parse(input: &str) -> Vec<Line> {
+        parse_with_options(input, &ParseOptions::default())
+    }
+
+    fn root(input: &str) -> Root {
+        parse_root(input, &ParseOptions::default())
+    }
+
+    #[test]
+    fn opml_nests_domains_and_escapes_attributes() {
+        let lines = parse("+ Tom & Jerry\n  + \"quotes\"\n    search = https://example.com/?a=1&b=<2>\n  note\nlast = x");
+
+        assert_eq!(
+            to_opml(&lines, "a < b"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+  <head>
+    <title>a &lt; b</title>
+  </head>
+  <body>
+    <outline text="Tom &amp; Jerry">
+      <outline text="&quot;quotes&quot;">
+        <outline text="search" htmlUrl="https://example.com/?a=1&amp;b=&lt;2&gt;"/>
+      </outline>
+      <outline text="note"/>
+    </outline>
+    <outline text="last" htmlUrl="x"/>
+  </body>
+</opml>
+"#
+        );
+    }
+
+    #[test]
+    fn schema_lists_domains_and_child_kinds() {
+        let schema = describe(&root("intro\n+ tools\n  vim = v\n  + editors\n    note\n+ empty\n+ tools\n  guide > empty"));
+
+        assert_eq!(schema.top_level, vec!["atom", "domain"]);
+        assert_eq!(
+            schema.domains,
+            vec![
+                DomainShape {
+                    path: vec!["tools".to_string()],
+                    child_kinds: vec!["link", "domain", "reference"],
+                },
+                DomainShape {
+                    path: vec!["tools".to_string(), "editors".to_string()],
+                    child_kinds: vec!["atom"],
+                },
+                DomainShape {
+                    path: vec!["empty".to_string()],
+                    child_kinds: vec![],
+                },
+            ]
+        );
+        assert_eq!(
+            schema.to_json(),
+            r#"{"top_level":["atom","domain"],"domains":[{"path":["tools"],"child_kinds":["link","domain","reference"]},{"path":["tools","editors"],"child_kinds":["atom"]},{"path":["empty"],"child_kinds":[]}]}"#
+        );
+    }
+
+    #[test]
+    fn numbered_outline_restarts_each_level() {
+        let root = root("+ tools\n  vim = https://vim.org\n  + editors\n    note\n  guide > tools\n+ music\n  radio = r");
+
+        assert_eq!(
+            to_outline(&root, Bullet::Numbered),
+            "1. tools\n  1. vim (https://vim.org)\n  2. editors\n    1. note\n  3. guide → tools\n2. music\n  1. radio (r)\n"
+        );
+        assert_eq!(to_outline(&root, Bullet::Dash).lines().next(), Some("- tools"));
+        assert_eq!(to_outline(&root, Bullet::Star).lines().nth(1), Some("  * vim (https://vim.org)"));
+    }
+
+    #[test]
+    fn jsonl_lines_point_at_their_parents() {
+        let jsonl = to_jsonl(&root("+ tools\n  vim = https://vim.org\n  + more\n    note\nguide > tools"));
+
+        assert_eq!(
+            jsonl.lines().collect::<Vec<_>>(),
+            vec![
+                r#"{"depth":0,"kind":"domain","text":"tools","parent_index":null}"#,
+                r#"{"depth":1,"kind":"link","text":"vim","parent_index":0,"value":"https://vim.org"}"#,
+                r#"{"depth":1,"kind":"domain","text":"more","parent_index":0}"#,
+                r#"{"depth":2,"kind":"atom","text":"note","parent_index":2}"#,
+                r#"{"depth":0,"kind":"reference","text":"guide","parent_index":null,"value":"tools"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn json_nests_children_under_their_parents() {
+        assert_eq!(
+            to_json(&root("+ tools\n  vim = https://vim.org\nnote")),
+            r#"[{"kind":"domain","text":"tools","children":[{"kind":"link","text":"vim","value":"https://vim.org"}]},{"kind":"atom","text":"note"}]"#
+        );
+    }
+
+    #[test]
+    fn exports_keep_links_in_document_order() {
+        let input = "z = 1\n+ web\n  + deep\n    + deeper\n      y = 2\n    x = 3\n  w = 4\n+ alpha\n  v = 5\nu = 6";
+        let root = root(input);
+        let order = ["z", "y", "x", "w", "v", "u"];
+
+        assert_eq!(root.links().map(|link| link.name).collect::<Vec<_>>(), order);
+
+        let positions = |output: &str, pattern: &str| -> Vec<usize> {
+            order.iter().map(|name| output.find(&pattern.replace("{}", name)).unwrap()).collect()
+        };
+        for output_positions in [
+            positions(&to_jsonl(&root), r#""text":"{}""#),
+            positions(&to_json(&root), r#""text":"{}""#),
+            positions(&to_opml(&root.lines, "t"), r#"text="{}""#),
+        ] {
+            assert!(output_positions.is_sorted());
+        }
+    }
+
+    #[test]
+    fn merging_is_the_exception_to_document_order() {
+        let options = ParseOptions {
+            merge_same_name_domains: true,
+            ..ParseOptions::default()
+        };
+        let root = parse_root("+ a\n  x = 1\n+ b\n  y = 2\n+ a\n  z = 3", &options);
+
+        assert_eq!(root.links().map(|link| link.name).collect::<Vec<_>>(), ["x", "z", "y"]);
+    }
+}
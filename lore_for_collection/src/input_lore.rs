@@ -1,18 +1,971 @@
-use std::path::Path;
-use std::fs;
-use crate::line::Line;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::io::{BufRead, Read};
+use std::{fs, io, thread};
+use crate::error::{Diagnostic, ParseError, ParseErrorKind, Severity};
+use crate::line::{Content, Line};
+use crate::line::INDENT_UNIT;
+use crate::parser::{Ambiguity, AmbiguityKind, CheckOptions, DefaultDialect, Dialect, IndentModel, NoDomainDialect, ParseOptions, SpaceIndent};
+use crate::root::Root;
 
 // 根据文件路径获取文件
 pub fn input_lore_file(path: &Path) -> String {
     fs::read_to_string(path).unwrap()
 }
 
+// 从路径中取出不带扩展名的文件名，`/` 和 `\` 在所有平台上都当作分隔符，
+// 取不到文件名时（比如以分隔符结尾）返回 None，由调用方决定默认值
+pub fn extract_filename(path: &str) -> Option<&str> {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+
+    // 去掉最后一个扩展名，以 `.` 开头的隐藏文件保持不变
+    let stem = match name.rfind('.') {
+        Some(dot) if dot > 0 => &name[..dot],
+        _ => name,
+    };
+
+    if stem.is_empty() {
+        None
+    } else {
+        Some(stem)
+    }
+}
+
+// 根据文件路径获取文件，文件超过 max_bytes 时返回错误，None 表示不限制大小
+//
+// 读取之前先通过元数据检查大小，读取时也最多只读到上限，防止文件在此期间变大
+pub fn input_lore_file_limited(path: &Path, max_bytes: Option<u64>) -> io::Result<String> {
+    let Some(max_bytes) = max_bytes else {
+        return fs::read_to_string(path);
+    };
+
+    let too_large = |size: u64| {
+        io::Error::new(
+            io::ErrorKind::FileTooLarge,
+            format!("{} is {} bytes, larger than the limit of {} bytes", path.display(), size, max_bytes),
+        )
+    };
+
+    let size = fs::metadata(path)?.len();
+    if size > max_bytes {
+        return Err(too_large(size));
+    }
+
+    let mut content = String::new();
+    fs::File::open(path)?.take(max_bytes + 1).read_to_string(&mut content)?;
+
+    if content.len() as u64 > max_bytes {
+        return Err(too_large(content.len() as u64));
+    }
+
+    Ok(content)
+}
+
+// 并行读取并解析多个文件，结果的顺序与输入相同
+pub fn parse_files(paths: Vec<PathBuf>) -> Vec<(PathBuf, io::Result<Root>)> {
+    // 按照 cpu 数量把文件分成若干组，每组一个线程
+    let workers = thread::available_parallelism().map_or(1, |count| count.get());
+    let chunk = paths.len().div_ceil(workers).max(1);
+
+    let results: Vec<io::Result<Root>> = thread::scope(|scope| {
+        let handles: Vec<_> = paths
+            .chunks(chunk)
+            .map(|group| {
+                scope.spawn(move || {
+                    group
+                        .iter()
+                        .map(|path| {
+                            let content = fs::read_to_string(path)?;
+                            Ok(parse_root(&content, &ParseOptions::default()))
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    paths.into_iter().zip(results).collect()
+}
+
+// 从任意 reader 中逐行读取并解析，跳过空行，不需要把整个文件读进内存
+pub fn lines_of<R: BufRead>(reader: R) -> impl Iterator<Item = io::Result<Line>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|(index, line)| line.map(|line| crate::parser::parse_line(index + 1, &line)))
+}
+
 // 把文件分成一行一行的然后去除空行
 pub fn parse(input: String) -> Vec<Line> {
+    parse_with_options(&input, &ParseOptions::default())
+}
+
+// 按照解析选项解析文件
+pub fn parse_with_options(input: &str, options: &ParseOptions) -> Vec<Line> {
+    parse_numbered(input.split('\n').enumerate(), options, &SpaceIndent(INDENT_UNIT))
+}
+
+// 用指定的方言解析文件
+pub fn parse_with_dialect(input: &str, dialect: &impl Dialect) -> Vec<Line> {
+    input
+        .split('\n')
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| crate::parser::parse_line_with(index + 1, line, dialect))
+        .collect()
+}
+
+// 用指定的缩进写法解析文件，比如用制表符缩进的文件可以用 TabIndent
+pub fn parse_with_indent(input: &str, model: &impl IndentModel) -> Vec<Line> {
     input
-        .as_str()
         .split('\n')
-        .filter(|line| !line.trim().is_empty())
-        .map(crate::parser::parse_line)
+        .enumerate()
+        .filter(|(_, line)| !model.strip(line).trim().is_empty())
+        .map(|(index, line)| crate::parser::parse_line_indented(index + 1, line, &DefaultDialect, model))
+        .collect()
+}
+
+// 解析后对每一行的内容做一次变换，比如展开简写的链接，变换不能改变缩进
+pub fn parse_with_transform(input: &str, mut transform: impl FnMut(Content) -> Content) -> Vec<Line> {
+    parse_with_options(input, &ParseOptions::default())
+        .into_iter()
+        .map(|line| Line {
+            content: transform(line.content),
+            ..line
+        })
         .collect()
 }
+
+// 只解析到指定的深度，用于快速查看大文件的大纲
+//
+// 每个被截断的分支换成一个 Elided 标记，记录被省略的后代数量，
+// 标记挂在保留下来的最深一级节点下面
+pub fn parse_outline(input: &str, depth: usize) -> Vec<Line> {
+    let mut lines: Vec<Line> = Vec::new();
+
+    // 当前路径上各节点的缩进，长度就是下一行的深度
+    let mut stack: Vec<usize> = Vec::new();
+
+    // 正在累计的省略标记：挂在哪个缩进下，以及省略了多少行
+    let mut pending: Option<(usize, usize, usize)> = None; // (缩进, 数量, 第一行的行号)
+
+    for line in parse_with_options(input, &ParseOptions::default()) {
+        while stack.last().is_some_and(|&indent| indent >= line.indent) {
+            stack.pop();
+        }
+        let line_depth = stack.len();
+        stack.push(line.indent);
+
+        if line_depth >= depth {
+            // 同一个分支中的行累计到同一个标记上
+            let indent = match line_depth {
+                0 => 0,
+                _ => stack[depth.saturating_sub(1)] + 1,
+            };
+            match &mut pending {
+                Some((_, count, _)) => *count += 1,
+                None => pending = Some((indent, 1, line.line_no)),
+            }
+            continue;
+        }
+
+        // 回到保留的深度，结束之前的分支
+        if let Some((indent, count, line_no)) = pending.take() {
+            lines.push(elided(indent, count, line_no));
+        }
+        lines.push(line);
+    }
+
+    if let Some((indent, count, line_no)) = pending {
+        lines.push(elided(indent, count, line_no));
+    }
+
+    lines
+}
+
+// 省略标记
+fn elided(indent: usize, count: usize, line_no: usize) -> Line {
+    Line {
+        line_no,
+        ..Line::new(indent, Content::Elided(count))
+    }
+}
+
+// 解析带有元数据的文档
+//
+// 文档开头可以有一段由 `---` 包围的元数据，每行是 `key: value`，
+// 没有闭合的 `---` 时视为没有元数据，整个文件都按正文解析
+pub fn parse_root(input: &str, options: &ParseOptions) -> Root {
+    parse_root_with_indent(input, options, &SpaceIndent(INDENT_UNIT))
+}
+
+// 用指定的缩进写法解析带有元数据的文档，元数据的写法不变
+pub fn parse_root_with_indent(input: &str, options: &ParseOptions, model: &impl IndentModel) -> Root {
+    let raw_lines: Vec<&str> = input.split('\n').collect();
+
+    // 第一个非空行必须是 `---`
+    let open = raw_lines.iter().position(|line| !line.trim().is_empty());
+    let close = open
+        .filter(|&open| raw_lines[open].trim() == "---")
+        .and_then(|open| {
+            raw_lines[open + 1..]
+                .iter()
+                .position(|line| line.trim() == "---")
+                .map(|offset| (open, open + 1 + offset))
+        });
+
+    let mut root = match close {
+        Some((open, close)) => {
+            // 解析元数据，忽略不是 `key: value` 的行
+            let metadata: HashMap<String, String> = raw_lines[open + 1..close]
+                .iter()
+                .filter_map(|line| line.split_once(':'))
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                .filter(|(key, _)| !key.is_empty())
+                .collect();
+
+            // 正文从元数据之后开始，行号保持不变
+            let body = raw_lines.iter().copied().enumerate().skip(close + 1);
+
+            Root {
+                metadata,
+                lines: parse_numbered(body, options, model),
+            }
+        },
+        None => Root {
+            metadata: HashMap::new(),
+            lines: parse_numbered(raw_lines.iter().copied().enumerate(), options, model),
+        },
+    };
+
+    if options.meta_domain {
+        take_meta_domain(&mut root);
+    }
+
+    root
+}
+
+// 把开头的 `+ @meta` 和它的子节点从正文中取出，放进元数据，不覆盖 `---` 中已有的键
+fn take_meta_domain(root: &mut Root) {
+    if !root.lines.first().is_some_and(|line| line.content == Content::Domain("@meta".to_string())) {
+        return;
+    }
+
+    let end = crate::tree::subtree_end(&root.lines, 0);
+    for line in root.lines.drain(..end).skip(1) {
+        let entry = match &line.content {
+            Content::Link(key, value) => Some((key.clone(), value.clone())),
+            Content::Atom(atom) => atom
+                .split_once(':')
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string())),
+            _ => None,
+        };
+
+        if let Some((key, value)) = entry.filter(|(key, _)| !key.is_empty()) {
+            root.metadata.entry(key).or_insert(value);
+        }
+    }
+}
+
+// 与 parse_root 相同，但按照 check 中的选项检查，比如不允许空文档时，对没有任何正文和元数据的输入返回错误
+pub fn try_parse_root(input: &str, options: &ParseOptions, check: &CheckOptions) -> Result<Root, ParseError> {
+    let root = parse_root(input, options);
+
+    if !check.allow_empty && root.lines.is_empty() && root.metadata.is_empty() {
+        return Err(ParseError::empty());
+    }
+
+    if check.strict_domains {
+        let raw_lines: Vec<&str> = input.split('\n').collect();
+        for line in &root.lines {
+            check_strict_domain(line, raw_lines[line.line_no - 1])?;
+        }
+    }
+
+    Ok(root)
+}
+
+// 解析并检查所有引用，有悬空或循环的引用时返回每一个出错的引用，否则与 parse_root 相同
+//
+// 循环的引用是指向包含它的 domain（或更上层的同名 domain）的引用
+pub fn parse_root_strict(input: &str, options: &ParseOptions) -> Result<Root, Vec<ParseError>> {
+    let root = parse_root(input, options);
+    let raw_lines: Vec<&str> = input.split('\n').collect();
+
+    let errors: Vec<ParseError> = root
+        .references()
+        .filter_map(|reference| {
+            let kind = match reference.resolved {
+                false => ParseErrorKind::DanglingReference,
+                true => {
+                    let index = root.lines.iter().position(|line| line.line_no == reference.line)?;
+                    let cyclic = crate::tree::ancestors(&root.lines, index)
+                        .iter()
+                        .any(|line| matches!(&line.content, Content::Domain(name) if name == reference.target));
+                    match cyclic {
+                        true => ParseErrorKind::CyclicReference,
+                        false => return None,
+                    }
+                },
+            };
+
+            // 列号指向引用的目标
+            let raw = raw_lines[reference.line - 1];
+            let column = raw.rfind(reference.target).map_or(1, |pos| raw[..pos].chars().count() + 1);
+            let message = match kind {
+                ParseErrorKind::DanglingReference => format!("reference `{}` points to `{}`, which is not a domain in this document", reference.name, reference.target),
+                _ => format!("reference `{}` points to `{}`, which contains it", reference.name, reference.target),
+            };
+
+            Some(ParseError {
+                kind,
+                ..ParseError::new(reference.line, column, message)
+            })
+        })
+        .collect();
+
+    match errors.is_empty() {
+        true => Ok(root),
+        false => Err(errors),
+    }
+}
+
+// 严格模式下 link 和引用必须写在 domain 下面的行里
+fn check_strict_domain(line: &Line, raw: &str) -> Result<(), ParseError> {
+    if let Content::Domain(name) = &line.content
+        && let Some(pos) = raw.find(['=', '>'])
+    {
+        return Err(ParseError::new(
+            line.line_no,
+            raw[..pos].chars().count() + 1,
+            format!("domain `{}` has content after its name, put it on an indented line", name),
+        ));
+    }
+
+    Ok(())
+}
+
+// 解析带行号的行，行号从 0 开始计数
+fn parse_numbered<'a>(input: impl Iterator<Item = (usize, &'a str)>, options: &ParseOptions, model: &impl IndentModel) -> Vec<Line> {
+    // 当前仍然打开的 domain 的缩进
+    let mut domains: Vec<usize> = Vec::new();
+
+    let mut lines = Vec::new();
+
+    // 连续的空行数
+    let mut blanks = 0;
+
+    for (index, raw) in input {
+        if model.strip(raw).trim().is_empty() {
+            blanks += 1;
+            continue;
+        }
+
+        let parse = |text| match options.no_domains {
+            true => crate::parser::parse_line_indented(index + 1, text, &NoDomainDialect, model),
+            false => crate::parser::parse_line_indented(index + 1, text, &DefaultDialect, model),
+        };
+
+        // 行尾注释只用于 link 和 atom
+        let (mut line, code) = match crate::parser::split_comment(raw) {
+            (code, Some(comment)) if options.trailing_comments => {
+                let mut line = parse(code);
+                match line.content {
+                    Content::Link(..) | Content::Atom(_) => {
+                        line.comment = Some(comment.to_string());
+                        (line, code)
+                    },
+                    _ => (parse(raw), raw),
+                }
+            },
+            _ => (parse(raw), raw),
+        };
+
+        if options.keep_blank_lines {
+            line.blank_before = blanks;
+        }
+
+        if options.preserve_separator && matches!(line.content, Content::Link(..)) {
+            line.separator = crate::token::tokenize(code)
+                .into_iter()
+                .find(|token| token.kind == crate::token::TokenKind::Separator)
+                .map(|token| code[token.range].to_string());
+        }
+        blanks = 0;
+
+        // 缩进不深于当前行的 domain 都已经结束
+        while domains.last().is_some_and(|&indent| indent >= line.indent) {
+            domains.pop();
+        }
+
+        // 不在任何 domain 里的 link 退回为 atom
+        if options.links_in_domains_only && domains.is_empty() && matches!(line.content, Content::Link(..)) {
+            line.content = Content::Atom(model.strip(code).trim_start().to_string());
+            line.pinned = false;
+        }
+
+        if let Content::Domain(_) = line.content {
+            domains.push(line.indent);
+        }
+
+        lines.push(line);
+    }
+
+    if options.merge_same_name_domains {
+        lines = crate::transform::merge_same_name_domains(lines);
+    }
+
+    lines
+}
+
+// 带检查的解析，遇到第一个错误就返回
+//
+// 比上一行深出一级以上的行会被当作上一行的子节点，缩进收回到上一行加一，
+// 并记录一条警告而不是错误
+pub fn parse_checked(input: &str) -> Result<(Vec<Line>, Vec<Diagnostic>), ParseError> {
+    parse_checked_with(input, &CheckOptions::default())
+}
+
+// 按照检查选项做带检查的解析
+pub fn parse_checked_with(input: &str, check: &CheckOptions) -> Result<(Vec<Line>, Vec<Diagnostic>), ParseError> {
+    parse_checked_with_indent(input, check, &SpaceIndent(INDENT_UNIT))
+}
+
+// 用指定的缩进写法做带检查的解析，缩进是否合法由 IndentModel::check 决定
+pub fn parse_checked_with_indent(input: &str, check: &CheckOptions, model: &impl IndentModel) -> Result<(Vec<Line>, Vec<Diagnostic>), ParseError> {
+    let mut lines: Vec<Line> = Vec::new();
+    let mut warnings = Vec::new();
+
+    for (index, raw) in input.split('\n').enumerate() {
+        if model.strip(raw).trim().is_empty() {
+            continue;
+        }
+
+        let mut line = crate::parser::check_line_indented(index + 1, raw, model)?;
+        if check.strict_domains {
+            check_strict_domain(&line, raw)?;
+        }
+
+        // 最多只能比上一行深一级
+        let max_indent = lines.last().map_or(0, |last| last.indent + 1);
+        if line.indent > max_indent {
+            warnings.push(Diagnostic {
+                line: line.line_no,
+                severity: Severity::Warning,
+                code: "over-indented",
+                message: format!("line is indented {} levels but at most {} is expected", line.indent, max_indent),
+            });
+            line.indent = max_indent;
+        }
+
+        lines.push(line);
+    }
+
+    if !check.allow_empty && lines.is_empty() {
+        return Err(ParseError::empty());
+    }
+
+    Ok((lines, warnings))
+}
+
+// 带恢复的检查解析，遇到错误时记录下来并继续，出错的行整行作为 atom 保留
+pub fn parse_recovering(input: &str) -> (Root, Vec<ParseError>) {
+    let mut root = parse_root(input, &ParseOptions::default());
+    let raw_lines: Vec<&str> = input.split('\n').collect();
+
+    let mut errors = Vec::new();
+
+    for line in &mut root.lines {
+        let raw = raw_lines[line.line_no - 1];
+
+        if let Err(error) = crate::parser::check_line(line.line_no, raw) {
+            errors.push(error);
+            line.content = Content::Atom(raw.trim().to_string());
+            line.pinned = false;
+        }
+    }
+
+    (root, errors)
+}
+
+// 宽松解析，从不失败，同时报告所有需要猜测才能解析的行
+pub fn parse_lenient(input: &str) -> (Root, Vec<Ambiguity>) {
+    let root = parse_root(input, &ParseOptions::default());
+    let raw_lines: Vec<&str> = input.split('\n').collect();
+
+    let mut found = Vec::new();
+    let mut previous: Option<usize> = None;
+
+    for line in &root.lines {
+        found.extend(crate::parser::ambiguities(line.line_no, raw_lines[line.line_no - 1]));
+
+        // 过深的行会被当作上一行的子节点
+        let max_indent = previous.map_or(0, |indent| indent + 1);
+        if line.indent > max_indent {
+            found.push(Ambiguity {
+                line: line.line_no,
+                kind: AmbiguityKind::OverIndented,
+                assumed: "attached to the previous line".to_string(),
+            });
+        }
+        previous = Some(line.indent);
+    }
+
+    (root, found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_in_domains_only_keeps_root_links_as_atoms() {
+        let options = ParseOptions {
+            links_in_domains_only: true,
+            ..ParseOptions::default()
+        };
+        let lines = parse_with_options("a = b\n+ tools\n  a = b", &options);
+
+        assert_eq!(lines[0].content, Content::Atom("a = b".to_string()));
+        assert_eq!(lines[2].content, Content::Link("a".to_string(), "b".to_string()));
+    }
+
+    #[test]
+    fn root_links_are_links_by_default() {
+        let lines = parse_with_options("a = b\n+ tools\n  a = b", &ParseOptions::default());
+
+        assert_eq!(lines[0].content, Content::Link("a".to_string(), "b".to_string()));
+        assert_eq!(lines[2].content, Content::Link("a".to_string(), "b".to_string()));
+    }
+
+    #[test]
+    fn links_after_a_domain_closes_are_atoms_again() {
+        let options = ParseOptions {
+            links_in_domains_only: true,
+            ..ParseOptions::default()
+        };
+        let lines = parse_with_options("+ tools\n  a = b\nc = d", &options);
+
+        assert_eq!(lines[1].content, Content::Link("a".to_string(), "b".to_string()));
+        assert_eq!(lines[2].content, Content::Atom("c = d".to_string()));
+    }
+
+    #[test]
+    fn front_matter_becomes_metadata() {
+        let root = parse_root("---\ntitle: my collection\nauthor: me\n---\n+ links\n  a = b", &ParseOptions::default());
+
+        assert_eq!(root.title(), Some("my collection"));
+        assert_eq!(root.metadata.get("author").map(String::as_str), Some("me"));
+        assert_eq!(root.lines.len(), 2);
+
+        // 行号仍然是源文件中的行号
+        assert_eq!(root.lines[0].line_no, 5);
+    }
+
+    #[test]
+    fn document_without_front_matter_has_no_metadata() {
+        let root = parse_root("+ links\n  a = b", &ParseOptions::default());
+
+        assert!(root.metadata.is_empty());
+        assert_eq!(root.lines.len(), 2);
+    }
+
+    #[test]
+    fn unclosed_front_matter_is_body() {
+        let root = parse_root("---\ntitle: x\n+ links", &ParseOptions::default());
+
+        assert!(root.metadata.is_empty());
+        assert_eq!(root.lines[0].content, Content::Atom("---".to_string()));
+        assert_eq!(root.lines.len(), 3);
+    }
+
+    #[test]
+    fn malformed_front_matter_lines_are_ignored() {
+        let root = parse_root("---\ntitle: x\nnot a pair\n: no key\n---\na", &ParseOptions::default());
+
+        assert_eq!(root.metadata.len(), 1);
+        assert_eq!(root.title(), Some("x"));
+        assert_eq!(root.lines.len(), 1);
+    }
+
+    #[test]
+    fn over_indented_line_attaches_to_the_previous_line_with_a_warning() {
+        let (lines, warnings) = parse_checked("+ a\n  + b\n        deep\n  c").unwrap();
+
+        assert_eq!(lines[2].indent, 2);
+        assert_eq!(crate::tree::parents(&lines), vec![None, Some(0), Some(1), Some(0)]);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 3);
+        assert_eq!(warnings[0].severity, Severity::Warning);
+        assert_eq!(warnings[0].code, "over-indented");
+    }
+
+    #[test]
+    fn odd_indentation_is_still_an_error() {
+        let error = parse_checked("+ a\n   b").unwrap_err();
+
+        assert_eq!((error.line, error.column), (2, 4));
+    }
+
+    #[test]
+    fn transform_expands_shorthand_links() {
+        let lines = parse_with_transform("+ code\n  lore = gh:FleetingLore/lore", |content| match content {
+            Content::Link(key, value) => match value.strip_prefix("gh:") {
+                Some(repo) => Content::Link(key, format!("https://github.com/{}", repo)),
+                None => Content::Link(key, value),
+            },
+            content => content,
+        });
+
+        assert_eq!(lines[1].indent, 1);
+        let html = crate::output::to_html("t", &lines, &crate::output::HtmlOptions::default());
+        assert!(html.contains(r#"href="https://github.com/FleetingLore/lore""#));
+        assert!(!html.contains("gh:"));
+    }
+
+    #[test]
+    fn lenient_parse_reports_every_guess() {
+        let (root, found) = parse_lenient("+ a\n   odd\n+b\n+\nx = y = z\n= value\n  + c\n      deep");
+        let report: Vec<(usize, AmbiguityKind)> = found.iter().map(|ambiguity| (ambiguity.line, ambiguity.kind)).collect();
+
+        assert_eq!(root.lines.len(), 8);
+        assert_eq!(
+            report,
+            vec![
+                (2, AmbiguityKind::OddIndent),
+                (3, AmbiguityKind::DomainWithoutSpace),
+                (4, AmbiguityKind::BareDomainMarker),
+                (5, AmbiguityKind::MultipleSeparators),
+                (6, AmbiguityKind::EmptyLinkPart),
+                (8, AmbiguityKind::OverIndented),
+            ]
+        );
+        assert_eq!(found[3].assumed, "split at the first `=` into `x` and `y = z`");
+    }
+
+    // 测试用的临时目录，每个测试一个
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lore-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rendered(root: &Root) -> Vec<String> {
+        root.lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn parallel_parse_matches_sequential_parse() {
+        let dir = temp_dir("parse-files");
+        let mut paths = Vec::new();
+        for i in 0..12 {
+            let path = dir.join(format!("{}.lore", i));
+            fs::write(&path, format!("+ file {}\n  link = https://example.com/{}\n  + nested\n    note {}", i, i, i)).unwrap();
+            paths.push(path);
+        }
+        paths.insert(5, dir.join("missing.lore"));
+
+        let results = parse_files(paths.clone());
+
+        assert_eq!(results.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(), paths);
+        for (path, result) in &results {
+            match fs::read_to_string(path) {
+                Ok(content) => assert_eq!(rendered(result.as_ref().unwrap()), rendered(&parse_root(&content, &ParseOptions::default()))),
+                Err(_) => assert!(result.is_err()),
+            }
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn outline_elides_deep_branches_with_counts() {
+        let lines = parse_outline("+ a\n  + b\n    c\n    + d\n      e\n  f\n    g\n+ h\nx", 2);
+        let outline: Vec<(usize, Content)> = lines.iter().map(|line| (line.indent, line.content.clone())).collect();
+
+        assert_eq!(
+            outline,
+            vec![
+                (0, Content::Domain("a".to_string())),
+                (1, Content::Domain("b".to_string())),
+                (2, Content::Elided(3)),
+                (1, Content::Atom("f".to_string())),
+                (2, Content::Elided(1)),
+                (0, Content::Domain("h".to_string())),
+                (0, Content::Atom("x".to_string())),
+            ]
+        );
+
+        // 标记的行号是第一个被省略的行
+        assert_eq!(lines[2].line_no, 3);
+        assert_eq!(lines[4].line_no, 7);
+    }
+
+    #[test]
+    fn outline_deep_enough_keeps_everything() {
+        let input = "+ a\n  + b\n    c";
+
+        assert_eq!(parse_outline(input, 3).len(), 3);
+        assert_eq!(parse_outline(input, 0).iter().map(|line| line.content.clone()).collect::<Vec<_>>(), vec![Content::Elided(3)]);
+    }
+
+    #[test]
+    fn file_just_over_the_limit_is_rejected() {
+        let dir = temp_dir("limited");
+        let path = dir.join("small.lore");
+        fs::write(&path, "+ a\n  b = c").unwrap();
+
+        let error = input_lore_file_limited(&path, Some(10)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::FileTooLarge);
+        assert!(error.to_string().contains("11 bytes, larger than the limit of 10 bytes"));
+
+        assert_eq!(input_lore_file_limited(&path, Some(11)).unwrap(), "+ a\n  b = c");
+        assert_eq!(input_lore_file_limited(&path, None).unwrap(), "+ a\n  b = c");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn filenames_come_from_any_separator() {
+        assert_eq!(extract_filename(r"C:\dir\file.lore"), Some("file"));
+        assert_eq!(extract_filename("/dir/file.lore"), Some("file"));
+        assert_eq!(extract_filename("file"), Some("file"));
+        assert_eq!(extract_filename("dir/archive.tar.lore"), Some("archive.tar"));
+        assert_eq!(extract_filename(".hidden"), Some(".hidden"));
+    }
+
+    #[test]
+    fn paths_without_a_name_have_no_filename() {
+        assert_eq!(extract_filename("https://example.com/"), None);
+        assert_eq!(extract_filename(r"C:\dir\"), None);
+        assert_eq!(extract_filename(""), None);
+    }
+
+    #[test]
+    fn no_domains_keeps_plus_lines_as_text() {
+        let options = ParseOptions {
+            no_domains: true,
+            ..ParseOptions::default()
+        };
+        let lines = parse_with_options("+ foo\n  + bar\n+ a = b\n* + pinned", &options);
+
+        assert!(lines.iter().all(|line| !matches!(line.content, Content::Domain(_))));
+        assert_eq!(lines[0].content, Content::atom_literal("+ foo"));
+        assert_eq!(lines[1].content, Content::atom_literal("+ bar"));
+        assert_eq!(lines[2].content, Content::Link("+ a".to_string(), "b".to_string()));
+        assert_eq!(lines[3].content, Content::atom_literal("* + pinned"));
+        assert!(!lines[3].pinned);
+    }
+
+    #[test]
+    fn streamed_lines_match_a_full_parse() {
+        let input = "+ links\n  a = b\n\n  + more\n    note\nlast > links\n";
+        let streamed: Vec<Line> = lines_of(io::Cursor::new(input)).collect::<io::Result<_>>().unwrap();
+        let parsed = parse(input.to_string());
+
+        assert_eq!(streamed.len(), parsed.len());
+        for (streamed, parsed) in streamed.iter().zip(&parsed) {
+            assert_eq!((streamed.indent, &streamed.content, streamed.line_no), (parsed.indent, &parsed.content, parsed.line_no));
+        }
+    }
+
+    #[test]
+    fn trailing_comments_split_off_links_and_atoms() {
+        let options = ParseOptions {
+            trailing_comments: true,
+            ..ParseOptions::default()
+        };
+        let lines = parse_with_options("+ tools\n  vim = https://x # my editor\n  docs = https://x/#frag\n  note \\# not a comment", &options);
+
+        assert_eq!(lines[1].content, Content::Link("vim".to_string(), "https://x".to_string()));
+        assert_eq!(lines[1].comment.as_deref(), Some("my editor"));
+        assert_eq!(lines[2].content, Content::Link("docs".to_string(), "https://x/#frag".to_string()));
+        assert_eq!(lines[2].comment, None);
+        assert_eq!(lines[3].content, Content::atom_literal("note \\# not a comment"));
+        assert_eq!(lines[3].comment, None);
+    }
+
+    #[test]
+    fn comment_only_lines_stay_text() {
+        let options = ParseOptions {
+            trailing_comments: true,
+            ..ParseOptions::default()
+        };
+        let lines = parse_with_options("+ tools\n  # TODO: fix\n  # a # b", &options);
+
+        assert_eq!(lines[1].content, Content::atom_literal("# TODO: fix"));
+        assert_eq!(lines[1].comment, None);
+        assert_eq!(lines[2].content, Content::atom_literal("# a # b"));
+        assert!(lines.iter().all(|line| line.content != Content::atom_literal("")));
+    }
+
+    #[test]
+    fn indented_whitespace_lines_are_blank_lines() {
+        let input = "+ a\n  x = 1\n    \n  y = 2";
+        let dropped = parse_with_options(input, &ParseOptions::default());
+        let kept = parse_with_options(input, &ParseOptions {
+            keep_blank_lines: true,
+            ..ParseOptions::default()
+        });
+
+        assert_eq!(dropped.len(), 3);
+        assert!(dropped.iter().all(|line| line.content != Content::atom_literal("")));
+        assert_eq!(dropped[2].blank_before, 0);
+        assert_eq!(kept.len(), 3);
+        assert_eq!(kept[2].blank_before, 1);
+    }
+
+    #[test]
+    fn separator_spacing_round_trips_only_when_preserved() {
+        let options = ParseOptions {
+            preserve_separator: true,
+            ..ParseOptions::default()
+        };
+
+        for line in ["a=b", "a = b", "a   =   b"] {
+            assert_eq!(parse_with_options(line, &options)[0].to_string(), line);
+            assert_eq!(parse_with_options(line, &ParseOptions::default())[0].to_string(), "a = b");
+        }
+    }
+
+    #[test]
+    fn meta_domain_fills_metadata() {
+        let options = ParseOptions {
+            meta_domain: true,
+            ..ParseOptions::default()
+        };
+        let root = parse_root("+ @meta\n  title = 收藏\n  author: me\n+ tools\n  vim = https://vim.org", &options);
+
+        assert_eq!(root.metadata.get("title").map(String::as_str), Some("收藏"));
+        assert_eq!(root.metadata.get("author").map(String::as_str), Some("me"));
+        assert_eq!(rendered(&root), vec!["+ tools", "  vim = https://vim.org"]);
+        assert_eq!(root.lines[0].line_no, 4);
+    }
+
+    #[test]
+    fn meta_domain_is_body_without_the_option_or_after_the_first_line() {
+        let input = "+ @meta\n  title = x";
+        assert_eq!(rendered(&parse_root(input, &ParseOptions::default())), vec!["+ @meta", "  title = x"]);
+
+        let options = ParseOptions {
+            meta_domain: true,
+            ..ParseOptions::default()
+        };
+        let root = parse_root("+ tools\n+ @meta\n  title = x", &options);
+        assert!(root.metadata.is_empty());
+        assert_eq!(root.lines.len(), 3);
+    }
+
+    #[test]
+    fn recovering_parse_keeps_the_good_lines() {
+        let (root, errors) = parse_recovering("+ tools\n  vim = https://vim.org\n   * + odd\n  git = https://git-scm.com");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 3);
+        assert_eq!(rendered(&root), vec!["+ tools", "  vim = https://vim.org", "  * + odd", "  git = https://git-scm.com"]);
+        assert_eq!(root.lines[2].content, Content::atom_literal("* + odd"));
+        assert!(!root.lines[2].pinned);
+    }
+
+    #[test]
+    fn empty_input_is_an_error_only_when_not_allowed() {
+        let options = ParseOptions::default();
+        let strict = CheckOptions {
+            allow_empty: false,
+            ..CheckOptions::default()
+        };
+
+        for input in ["", "\n  \n"] {
+            assert!(try_parse_root(input, &options, &CheckOptions::default()).unwrap().lines.is_empty());
+            assert_eq!(try_parse_root(input, &options, &strict).unwrap_err().kind, ParseErrorKind::Empty);
+            assert_eq!(parse_checked_with(input, &strict).unwrap_err().kind, ParseErrorKind::Empty);
+        }
+        assert!(try_parse_root("---\ntitle: x\n---\n", &options, &strict).is_ok());
+        assert!(try_parse_root("note", &options, &strict).is_ok());
+    }
+
+    #[test]
+    fn strict_domains_reject_content_on_the_domain_line() {
+        let strict = CheckOptions {
+            strict_domains: true,
+            ..CheckOptions::default()
+        };
+        let input = "+ news\n  hn = https://news.ycombinator.com\n+ tools = x";
+
+        let error = try_parse_root(input, &ParseOptions::default(), &strict).unwrap_err();
+        assert_eq!((error.line, error.column, error.kind), (3, 9, ParseErrorKind::Syntax));
+        assert_eq!(parse_checked_with(input, &strict).unwrap_err(), error);
+
+        // 宽松的解析把整行作为 domain 的名字
+        let root = try_parse_root(input, &ParseOptions::default(), &CheckOptions::default()).unwrap();
+        assert_eq!(root.lines[2].content, Content::Domain("tools = x".to_string()));
+        assert!(parse_checked_with("+ tools\n  a = b", &strict).is_ok());
+    }
+
+    // 每个行首的 `.` 算一级缩进
+    struct DotIndent;
+
+    impl IndentModel for DotIndent {
+        fn indent_of(&self, line: &str) -> usize {
+            line.chars().take_while(|&c| c == '.').count()
+        }
+
+        fn strip<'a>(&self, line: &'a str) -> &'a str {
+            line.trim_start_matches('.')
+        }
+    }
+
+    #[test]
+    fn custom_indent_model_is_used_by_every_parser() {
+        let input = "---\ntitle: dots\n---\n+ tools\n.vim = https://vim.org # editor\n.+ more\n..deep\n.\nlast = x";
+        let expected = vec!["+ tools", "  vim = https://vim.org # editor", "  + more", "    deep", "last = x"];
+
+        let options = ParseOptions {
+            trailing_comments: true,
+            ..ParseOptions::default()
+        };
+        let root = parse_root_with_indent(input, &options, &DotIndent);
+        assert_eq!(rendered(&root), expected);
+        assert_eq!(root.metadata.get("title").map(String::as_str), Some("dots"));
+        assert_eq!(root.lines[1].comment.as_deref(), Some("editor"));
+
+        let (lines, warnings) = parse_checked_with_indent("+ tools\n.vim = https://vim.org\n...deep", &CheckOptions::default(), &DotIndent).unwrap();
+        assert_eq!(lines.iter().map(|line| line.indent).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(warnings.len(), 1);
+
+        // 退回为 atom 的 link 同样按这个模型去掉缩进
+        let options = ParseOptions {
+            links_in_domains_only: true,
+            ..ParseOptions::default()
+        };
+        let root = parse_root_with_indent("x\n.a = b", &options, &DotIndent);
+        assert_eq!(root.lines[1].content, Content::Atom("a = b".to_string()));
+
+        let body = input.split("---\n").last().unwrap();
+        assert_eq!(crate::transform::autofix_indent_with(body, &DotIndent), "+ tools\n  vim = https://vim.org # editor\n  + more\n    deep\n\nlast = x");
+    }
+
+    #[test]
+    fn strict_parse_reports_every_bad_reference() {
+        let input = "+ docs\n  guide > docs\n  old > gone\n+ tools\n  docs > docs2\n  ok > docs";
+        let errors = parse_root_strict(input, &ParseOptions::default()).unwrap_err();
+
+        assert_eq!(
+            errors.iter().map(|error| (error.line, error.column, error.kind)).collect::<Vec<_>>(),
+            vec![
+                (2, 11, ParseErrorKind::CyclicReference),
+                (3, 9, ParseErrorKind::DanglingReference),
+                (5, 10, ParseErrorKind::DanglingReference),
+            ]
+        );
+        assert!(parse_root_strict("+ docs\n+ tools\n  ok > docs", &ParseOptions::default()).is_ok());
+        assert_eq!(parse_root(input, &ParseOptions::default()).references().count(), 4);
+    }
+}
@@ -0,0 +1,16 @@
+// 所有遍历、导出和检查的接口都按文档顺序（先序）给出节点，与源文件中行的顺序一致，
+// 同一个文档的输出总是相同的，可以直接用来比较差异；
+// 唯一的例外是 merge_same_name_domains，合并后的子节点排在第一次出现的 domain 下，line_no 不再递增
+pub mod line;
+pub mod parser;
+pub mod input_lore;
+pub mod output;
+pub mod error;
+pub mod tree;
+pub mod root;
+mod json;
+pub mod validate;
+pub mod transform;
+pub mod token;
+pub mod diff;
+pub mod export;
@@ -1,12 +1,205 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// 节点的标识，解析时按顺序分配，同一次解析中不会重复
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub u64);
+
+impl NodeId {
+    // 分配一个新的标识
+    pub fn next() -> NodeId {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        NodeId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+// 每级缩进的空格数
+pub const INDENT_UNIT: usize = 2;
+
 // 行的数据分为缩进和行内容
+#[derive(Debug)]
 pub struct Line {
     pub indent: usize,
     pub content: Content,
+    pub line_no: usize, // 源文件中的行号，从 1 开始
+    pub blank_before: usize, // 保留空行时，这一行之前的空行数
+    pub pinned: bool, // 以 `* ` 开头的 link 或 domain
+    pub comment: Option<String>, // 行尾 ` # ` 之后的注释
+    pub separator: Option<String>, // link 原来的 `=` 和两边的空白，输出时代替默认的 ` = `
+    pub(crate) id: NodeId,
 }
 
-// 行内容有三种
+impl Line {
+    // 新建一行，不来自源文件，行号为 0
+    pub fn new(indent: usize, content: Content) -> Line {
+        Line {
+            indent,
+            content,
+            line_no: 0,
+            blank_before: 0,
+            pinned: false,
+            comment: None,
+            separator: None,
+            id: NodeId::next(),
+        }
+    }
+
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    // 这一行的注释：行尾 ` # ` 之后的部分，或者整行注释 `# ...` 中 `# ` 之后的部分
+    //
+    // 行尾注释只有用 trailing_comments 解析时才会分出来，整行注释不论选项都是以 `# ` 开头的 atom
+    pub fn comment_text(&self) -> Option<&str> {
+        match (&self.comment, &self.content) {
+            (Some(comment), _) => Some(comment),
+            (None, Content::Atom(atom)) => atom.strip_prefix("# "),
+            _ => None,
+        }
+    }
+
+    // 整行注释，即以 `# ` 开头的 atom
+    pub fn is_comment(&self) -> bool {
+        matches!(&self.content, Content::Atom(atom) if atom.starts_with("# "))
+    }
+
+    // 注释开头的标记，比如 `TODO: ...` 中的 TODO，必须全部是大写字母并且紧跟着 `:`
+    pub fn comment_tag(&self) -> Option<&str> {
+        let (tag, _) = self.comment_text()?.split_once(':')?;
+
+        match !tag.is_empty() && tag.chars().all(|c| c.is_ascii_uppercase()) {
+            true => Some(tag),
+            false => None,
+        }
+    }
+
+    // 换成指定的缩进，其它部分不变
+    pub fn with_indent(self, indent: usize) -> Line {
+        Line {
+            indent,
+            ..self
+        }
+    }
+
+    // 输出为 lore 文本，每级缩进 unit 个空格，解析时使用的是 INDENT_UNIT
+    pub fn render(&self, unit: usize) -> String {
+        let marker = match self.pinned {
+            true => "* ",
+            false => "",
+        };
+
+        let content = match (&self.content, &self.separator) {
+            (Content::Link(key, value), Some(separator)) => format!("{}{}{}", key, separator, value),
+            (content, _) => content.to_string(),
+        };
+
+        let mut text = format!("{}{}{}", " ".repeat(self.indent * unit), marker, content);
+
+        if let Some(comment) = &self.comment {
+            text.push_str(&format!(" # {}", comment));
+        }
+
+        text
+    }
+}
+
+// 行内容有四种，另外还有大纲中表示被省略内容的标记
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Content {
     Atom(String), // 原子
     Link(String, String), // 链接
-    Domain(String) // 领域
+    Domain(String), // 领域
+    Reference(String, String), // 引用，`name > target`
+    Elided(usize) // 被省略的后代数量，只出现在 parse_outline 的结果中
+}
+
+impl Content {
+    // 不论文本是什么都作为 atom，比如以 `+ ` 开头的普通文字
+    pub fn atom_literal(text: impl Into<String>) -> Content {
+        Content::Atom(text.into())
+    }
+
+    // 种类的名字
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            Content::Atom(_) => "atom",
+            Content::Link(..) => "link",
+            Content::Domain(_) => "domain",
+            Content::Reference(..) => "reference",
+            Content::Elided(_) => "elided",
+        }
+    }
+}
+
+// 输出为 lore 文本
+impl fmt::Display for Content {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Content::Atom(atom) => write!(f, "{}", atom),
+            Content::Link(key, value) => write!(f, "{} = {}", key, value),
+            Content::Domain(domain) => write!(f, "+ {}", domain),
+            Content::Reference(name, target) => write!(f, "{} > {}", name, target),
+            Content::Elided(_) => write!(f, "…"),
+        }
+    }
+}
+
+// 输出为 lore 文本，每级缩进两个空格
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(INDENT_UNIT))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use crate::input_lore::parse_with_options;
+    use crate::parser::ParseOptions;
+
+    fn parse(input: &str) -> Vec<Line> {
+        parse_with_options(input, &ParseOptions::default())
+    }
+
+    #[test]
+    fn every_node_has_a_distinct_id() {
+        let lines = parse("+ a\n  x = 1\n  x = 1\n+ a\n  note");
+        let ids: HashSet<NodeId> = lines.iter().map(Line::id).collect();
+
+        assert_eq!(ids.len(), lines.len());
+    }
+
+    #[test]
+    fn one_line_renders_at_any_indent_and_unit() {
+        let rendered: Vec<String> = [0, 1, 3]
+            .into_iter()
+            .flat_map(|indent| {
+                let line = parse("vim = https://vim.org").remove(0).with_indent(indent);
+                [2, 4].map(|unit| line.render(unit))
+            })
+            .collect();
+
+        assert_eq!(
+            rendered,
+            vec![
+                "vim = https://vim.org",
+                "vim = https://vim.org",
+                "  vim = https://vim.org",
+                "    vim = https://vim.org",
+                "      vim = https://vim.org",
+                "            vim = https://vim.org",
+            ]
+        );
+    }
+
+    #[test]
+    fn references_render_in_canonical_form() {
+        for input in ["name>target", "name  >  target", "name > target"] {
+            let line = parse(input).remove(0);
+            assert_eq!(line.content, Content::Reference("name".to_string(), "target".to_string()));
+            assert_eq!(line.to_string(), "name > target");
+        }
+    }
 }
@@ -1,10 +1,8 @@
-mod line;
-mod parser;
-mod input_lore;
-mod output;
-
 use std::env;
 use std::path::Path;
+use lore_for_collection::{input_lore, output};
+use lore_for_collection::output::{HtmlOptions, Theme};
+use lore_for_collection::parser::ParseOptions;
 
 fn main() {
     // 接收命令行参数
@@ -18,8 +16,17 @@ fn main() {
     let content: String = input_lore::input_lore_file(input_path);
 
     // 解析文件
-    let target = input_lore::parse(content);
+    let root = input_lore::parse_root(&content, &ParseOptions::default());
+
+    // 标题优先使用元数据中的 title
+    let title = root.title().unwrap_or("Test").to_string();
+
+    // 主题来自元数据中的 theme，默认为 light
+    let options = HtmlOptions {
+        theme: root.metadata.get("theme").and_then(|name| Theme::from_name(name)).unwrap_or_default(),
+        ..HtmlOptions::default()
+    };
 
     // 生成 html 目标文件
-    output::output_html("Test", target, output_path);
+    output::output_html(&title, root.lines, output_path, &options);
 }
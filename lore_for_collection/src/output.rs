@@ -1,66 +1,457 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
+use crate::diff::{self, Change};
+use crate::export;
 use crate::line::{Content, Line};
+use crate::root::Root;
+use crate::tree;
 
-// 生成 html 文件
-pub fn output_html(title: &str, lines: Vec<Line>, path: &Path) {
-    let mut html = String::new();
+// 内置主题
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+    Compact,
+}
 
-    html.push_str(
-        format!(
-            r##"<!DOCTYPE html>
-<html>
-<head>
-<meta charset="UTF-8">
-<meta name="viewport" content="width=device-width, initial-scale=1.0">
-<title>{}</title>
-<style>
-* {{
+impl Theme {
+    // 根据名字选择主题，比如元数据中的 theme
+    pub fn from_name(name: &str) -> Option<Theme> {
+        match name.trim().to_lowercase().as_str() {
+            "light" => Some(Theme::Light),
+            "dark" => Some(Theme::Dark),
+            "compact" => Some(Theme::Compact),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+            Theme::Compact => "compact",
+        }
+    }
+
+    // 主题对应的样式
+    fn css(&self) -> &'static str {
+        match self {
+            Theme::Light => LIGHT_CSS,
+            Theme::Dark => DARK_CSS,
+            Theme::Compact => COMPACT_CSS,
+        }
+    }
+}
+
+// 链接打开的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinkTarget {
+    #[default]
+    Blank, // 新窗口打开，即 target="_blank"
+    Same, // 当前窗口打开，不输出 target
+}
+
+// 正文的排版方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    #[default]
+    Paragraphs, // 每行一个段落，用左边距表示深度
+    List, // 嵌套的 ul 和 li 列表
+    Sections, // 每个 domain 是一个 section，标题从 h2 开始随深度增加
+}
+
+// html 生成选项
+pub struct HtmlOptions {
+    pub theme: Theme,
+    pub layout: Layout,
+    pub link_target: LinkTarget,
+    pub link_rel: Option<String>, // 链接的 rel 属性
+    pub headings: bool, // 按深度把 atom 渲染成 h1 到 h6
+    pub accessible: bool, // 输出 main 地标以及 role、aria 属性，方便读屏软件导航
+    pub data_attributes: bool, // 给每个节点加上 data-kind、data-depth、data-path
+    pub breadcrumbs: bool, // 给 domain 加上锚点，并在它的内容之前显示从顶层到它的路径，只用于段落排版
+    pub default_scheme: Option<String>, // 给 `example.com` 这样没有协议的链接补上的协议，比如 https
+    pub base_url: Option<String>, // 相对链接前面加上的地址，同一个文档可以部署到不同的位置；先于 default_scheme 处理
+    pub icons: HashMap<String, String>, // domain 名字对应的图标，比如 emoji，显示在名字前面
+    pub compact_links: bool, // 子节点全部是 link 的 domain，把这些 link 放在同一行，只用于段落排版
+}
+
+impl Default for HtmlOptions {
+    fn default() -> HtmlOptions {
+        HtmlOptions {
+            theme: Theme::default(),
+            layout: Layout::default(),
+            link_target: LinkTarget::default(),
+            link_rel: Some("noopener noreferrer".to_string()),
+            headings: false,
+            accessible: false,
+            data_attributes: false,
+            breadcrumbs: false,
+            default_scheme: None,
+            base_url: None,
+            icons: HashMap::new(),
+            compact_links: false,
+        }
+    }
+}
+
+// 渲染某一种行的回调，返回这一行的 html 片段
+pub type RenderHook = Box<dyn Fn(&Line) -> String>;
+
+// 按行的种类替换默认的渲染方式，没有提供的种类使用默认渲染
+#[derive(Default)]
+pub struct HtmlHooks {
+    pub atom: Option<RenderHook>,
+    pub link: Option<RenderHook>,
+    pub domain: Option<RenderHook>,
+}
+
+const LIGHT_CSS: &str = r#"* {
     margin: 0;
     padding: 0;
     box-sizing: border-box;
-}}
+}
 
-body {{
+body {
     font-family: Arial, sans-serif;
     background-color: #f8f9fa;
     color: #212529;
     min-height: 100vh;
     padding: 2rem;
     line-height: 1.6;
-}}
+}
 
-p {{
+p {
     margin-bottom: 1rem;
-}}
+}
 
-a {{
+a {
     text-decoration: none;
     color: #010150;
     font-size: 1.1rem;
     font-weight: 500;
     transition: all 0.2s ease;
-}}
+}
 
-a:hover {{
+a:hover {
     color: #0ad3b6;
-}}
+}
 
-a:active {{
+a:active {
     color: #084298;
     text-decoration: underline;
-}}
+}
+
+.pinned {
+    background-color: #fff3cd;
+}
+"#;
+
+const DARK_CSS: &str = r#"* {
+    margin: 0;
+    padding: 0;
+    box-sizing: border-box;
+}
+
+body {
+    font-family: Arial, sans-serif;
+    background-color: #1e1f22;
+    color: #dfe1e5;
+    min-height: 100vh;
+    padding: 2rem;
+    line-height: 1.6;
+}
+
+p {
+    margin-bottom: 1rem;
+}
+
+a {
+    text-decoration: none;
+    color: #8ab4f8;
+    font-size: 1.1rem;
+    font-weight: 500;
+    transition: all 0.2s ease;
+}
+
+a:hover {
+    color: #0ad3b6;
+}
+
+a:active {
+    color: #c2d7fb;
+    text-decoration: underline;
+}
+
+.pinned {
+    background-color: #3d3722;
+}
+"#;
+
+const COMPACT_CSS: &str = r#"* {
+    margin: 0;
+    padding: 0;
+    box-sizing: border-box;
+}
+
+body {
+    font-family: Arial, sans-serif;
+    font-size: 0.9rem;
+    background-color: #ffffff;
+    color: #212529;
+    padding: 0.5rem;
+    line-height: 1.3;
+}
+
+p {
+    margin-bottom: 0.2rem;
+}
+
+a {
+    text-decoration: none;
+    color: #010150;
+    font-weight: 500;
+}
+
+a:hover {
+    color: #0ad3b6;
+}
+
+a:active {
+    color: #084298;
+    text-decoration: underline;
+}
+
+.pinned {
+    background-color: #fff3cd;
+}
+"#;
+
+// 生成 html 文件
+pub fn output_html(title: &str, lines: Vec<Line>, path: &Path, options: &HtmlOptions) {
+    fs::write(path, to_html(title, &lines, options)).unwrap();
+}
+
+// 生成 html 文本
+pub fn to_html(title: &str, lines: &[Line], options: &HtmlOptions) -> String {
+    to_html_with(title, lines, options, &HtmlHooks::default())
+}
+
+// 生成 html 文本，部分种类的行交给回调渲染
+pub fn to_html_with(title: &str, lines: &[Line], options: &HtmlOptions, hooks: &HtmlHooks) -> String {
+    page(title, &body(lines, 0, options, hooks), options)
+}
+
+// 粗略估计默认选项下 to_html 输出的字节数，不生成字符串，用于决定是否需要分页
+pub fn estimate_html_bytes(lines: &[Line]) -> usize {
+    // 页面的头部、样式和结尾
+    let page = 250 + LIGHT_CSS.len();
+
+    let body: usize = lines
+        .iter()
+        .map(|line| {
+            // `<p style="margin-left: 40px"></p>`
+            let paragraph = 34;
+
+            paragraph + match &line.content {
+                Content::Atom(atom) => atom.len(),
+                // `<a href="" target="_blank" rel="noopener noreferrer"></a>`
+                Content::Link(key, value) => 59 + key.len() + value.len(),
+                // `<strong>+ </strong>`
+                Content::Domain(domain) => 19 + domain.len(),
+                // ` → <em></em>`
+                Content::Reference(name, target) => 14 + name.len() + target.len(),
+                Content::Elided(_) => 20,
+            }
+        })
+        .sum();
+
+    page + body
+}
+
+// 只生成路径所指的子树，找不到路径时返回 None
+pub fn to_html_subtree(lines: &[Line], path: &[&str], title: &str, options: &HtmlOptions) -> Option<String> {
+    Some(page(title, &to_html_fragment(lines, path, options)?, options))
+}
+
+// 只生成路径所指子树的 html 片段，没有 html、head 等页面结构，方便按需加载后插入页面
+pub fn to_html_fragment(lines: &[Line], path: &[&str], options: &HtmlOptions) -> Option<String> {
+    let index = tree::find_path(lines, path)?;
+    let end = tree::subtree_end(lines, index);
 
+    // 子树的根从最左边开始显示
+    Some(body(&lines[index..end], lines[index].indent, options, &HtmlHooks::default()))
+}
+
+// 只生成 domain 组成的目录，省略所有 atom、link 和引用，没有子节点的 domain 也会显示
+pub fn to_html_domains_only(title: &str, lines: &[Line], options: &HtmlOptions) -> String {
+    let domains = lines.iter().filter(|line| matches!(line.content, Content::Domain(_)));
+    page(title, &body(domains, 0, options, &HtmlHooks::default()), options)
+}
+
+// 生成一个内嵌文档 json 的页面，由页面中的脚本把 json 显示成可以折叠的树，点击 domain 折叠或展开
+pub fn to_json_tree_page(title: &str, root: &Root, options: &HtmlOptions) -> String {
+    // `</` 会提前结束 script 标签，在 json 中写成 `<\/` 意思不变
+    let json = export::to_json(root).replace("</", "<\\/");
+
+    let body = format!(
+        r#"<div id="lore-tree"></div>
+<script type="application/json" id="lore-data">{}</script>
+<script>
+(function () {{
+    var data = JSON.parse(document.getElementById("lore-data").textContent);
+
+    function render(nodes) {{
+        var list = document.createElement("ul");
+        nodes.forEach(function (node) {{
+            var item = document.createElement("li");
+            var label = document.createElement(node.kind === "link" ? "a" : "span");
+            label.textContent = node.kind === "domain" ? "+ " + node.text : node.text;
+            if (node.kind === "link") {{
+                label.href = node.value;
+            }}
+            item.appendChild(label);
+
+            if (node.children) {{
+                var children = render(node.children);
+                item.appendChild(children);
+                if (node.kind !== "link") {{
+                    label.style.cursor = "pointer";
+                    label.addEventListener("click", function () {{
+                        children.hidden = !children.hidden;
+                    }});
+                }}
+            }}
+            list.appendChild(item);
+        }});
+        return list;
+    }}
+
+    document.getElementById("lore-tree").appendChild(render(data));
+}})();
+</script>"#,
+        json
+    );
+
+    page(title, &body, options)
+}
+
+// 生成带行号的源码视图，原样显示源文件的每一行（包括空行），按种类加上 kind-atom、kind-link 这样的 class，
+// 空行的 class 是 kind-blank，方便做语法高亮
+pub fn to_html_source_view(title: &str, source: &str, options: &HtmlOptions) -> String {
+    let raw_lines: Vec<&str> = source.lines().collect();
+
+    // 行号栏的宽度
+    let width = raw_lines.len().to_string().len();
+
+    let rows: String = raw_lines
+        .iter()
+        .enumerate()
+        .map(|(index, raw)| {
+            let kind = match raw.trim().is_empty() {
+                true => "blank",
+                false => crate::parser::parse_line(index + 1, raw).content.kind_name(),
+            };
+
+            format!(
+                r#"<span class="line kind-{}"><span class="line-no">{:>width$}</span> {}</span>"#,
+                kind,
+                index + 1,
+                escape_attribute(raw),
+                width = width
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    page(title, &format!(r#"<pre class="source">{}</pre>"#, rows), options)
+}
+
+// 渲染新文档，并标出相对旧文档新增（绿色）和删除（删除线）的行
+pub fn to_html_diff(title: &str, old: &[Line], new: &[Line], options: &HtmlOptions) -> String {
+    let body: String = diff::diff(old, new)
+        .into_iter()
+        .map(|change| match change {
+            Change::Same(line) => line_to_html(line, 0, options, ""),
+            Change::Added(line) => format!(
+                r#"<div class="added" style="background-color: #e6ffec">{}</div>"#,
+                line_to_html(line, 0, options, "")
+            ),
+            Change::Removed(line) => format!(
+                r#"<div class="removed" style="color: #b31d28; text-decoration: line-through">{}</div>"#,
+                line_to_html(line, 0, options, "")
+            ),
+        })
+        .collect();
+
+    page(title, &body, options)
+}
+
+// 把新旧两个文档并排显示在两栏中，相同的行对齐在同一行，删除和新增的行只出现在一边
+pub fn to_html_side_by_side(title: &str, old: &[Line], new: &[Line], options: &HtmlOptions) -> String {
+    let rows: String = diff::diff(old, new)
+        .into_iter()
+        .map(|change| {
+            let removed = r#" style="background-color: #ffebe9""#;
+            let added = r#" style="background-color: #e6ffec""#;
+
+            let (left, left_style, right, right_style) = match change {
+                Change::Same(line) => (line_to_html(line, 0, options, ""), "", line_to_html(line, 0, options, ""), ""),
+                Change::Removed(line) => (line_to_html(line, 0, options, ""), removed, String::new(), ""),
+                Change::Added(line) => (String::new(), "", line_to_html(line, 0, options, ""), added),
+            };
+
+            format!(
+                r#"<tr><td class="old"{}>{}</td><td class="new"{}>{}</td></tr>"#,
+                left_style,
+                left,
+                right_style,
+                right
+            )
+        })
+        .collect();
+
+    let body = format!(
+        r#"<table class="side-by-side" style="width: 100%; table-layout: fixed; border-collapse: collapse"><tr><th>old</th><th>new</th></tr>{}</table>"#,
+        rows
+    );
+
+    page(title, &body, options)
+}
+
+// 把正文包装成完整的页面
+fn page(title: &str, body: &str, options: &HtmlOptions) -> String {
+    let mut html = String::new();
+
+    html.push_str(
+        format!(
+            r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="UTF-8">
+<meta name="viewport" content="width=device-width, initial-scale=1.0">
+<title>{}</title>
+<style>
+{}
 </style>
 </head>
-<body>
+<body class="theme-{}">
 "##,
-            title,
+            escape_attribute(title),
+            options.theme.css(),
+            options.theme.name(),
         ).as_str()
     );
 
-    for line in lines {
-        html.push_str(line_to_html(&line).as_str());
+    // 正文放在 main 地标里
+    if options.accessible {
+        html.push_str(&format!(r#"<main aria-label="{}">"#, escape_attribute(title)));
+        html.push_str(body);
+        html.push_str("</main>");
+    } else {
+        html.push_str(body);
     }
 
     html.push_str(
@@ -69,21 +460,300 @@ a:active {{
 </html>"#
     );
 
-    fs::write(path, html).unwrap();
+    html
 }
 
-// 以行为单位的转换
-fn line_to_html(line: &Line) -> String {
+// 生成正文，base 是显示在最左边的缩进
+fn body<'a>(lines: impl IntoIterator<Item = &'a Line>, base: usize, options: &HtmlOptions, hooks: &HtmlHooks) -> String {
+    match options.layout {
+        Layout::List => return list_body(lines, options, hooks),
+        Layout::Sections => return sections_body(lines, base, options, hooks),
+        Layout::Paragraphs => {},
+    }
+
+    let lines: Vec<&Line> = lines.into_iter().collect();
+
+    // 祖先的缩进和 domain 名字，用来计算 data-depth 和 data-path
+    let mut stack: Vec<(usize, Option<&str>)> = Vec::new();
+
+    // 已经合并到同一行中的 link 到这里为止
+    let mut skip_until = 0;
+
+    // 开启面包屑时祖先 domain 的缩进和锚点，以及已经用过的锚点
+    let mut trail: Vec<(usize, String)> = Vec::new();
+    let mut used: HashSet<String> = HashSet::new();
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(index, &line)| {
+            if index < skip_until {
+                return String::new();
+            }
+
+            while stack.last().is_some_and(|&(indent, _)| indent >= line.indent) {
+                stack.pop();
+            }
+            while trail.last().is_some_and(|&(indent, _)| indent >= line.indent) {
+                trail.pop();
+            }
+
+            let mut extra = node_extra(line, &stack, options);
+
+            stack.push((line.indent, match &line.content {
+                Content::Domain(name) => Some(name),
+                _ => None,
+            }));
+
+            // 从顶层到这个 domain 的各级名字
+            let path: Vec<&str> = match (&line.content, options.breadcrumbs) {
+                (Content::Domain(_), true) => stack.iter().filter_map(|&(_, name)| name).collect(),
+                _ => Vec::new(),
+            };
+            if let Some(name) = path.last() {
+                let parent = trail.last().map(|(_, id)| id.as_str());
+                let id = unique_anchor(domain_anchor(parent, name), &mut used);
+                extra.push_str(&format!(r#" id="{}""#, id));
+                trail.push((line.indent, id));
+            }
+
+            let mut html = spacer(line);
+            match hook(line, hooks) {
+                Some(hook) => html.push_str(&hook(line)),
+                None => html.push_str(&line_to_html(line, base, options, &extra)),
+            }
+
+            if !path.is_empty() {
+                let ids: Vec<&str> = trail.iter().map(|(_, id)| id.as_str()).collect();
+                html.push_str(&breadcrumb(&path, &ids, (line.indent.saturating_sub(base) + 1) * 20));
+            }
+
+            // 子节点全部是同一级的 link 时放在同一行
+            if options.compact_links && hooks.link.is_none() && matches!(line.content, Content::Domain(_)) {
+                let end = index + 1 + lines[index + 1..].iter().take_while(|child| child.indent > line.indent).count();
+                let children = &lines[index + 1..end];
+
+                if !children.is_empty()
+                    && children.iter().all(|child| matches!(child.content, Content::Link(..)) && child.indent == children[0].indent)
+                {
+                    html.push_str(&compact_links(children, base, options));
+                    skip_until = end;
+                }
+            }
+
+            html
+        })
+        .collect()
+}
+
+// 把几个 link 放在同一行，用 ` · ` 分开
+fn compact_links(links: &[&Line], base: usize, options: &HtmlOptions) -> String {
+    let anchors: Vec<String> = links
+        .iter()
+        .filter_map(|line| match &line.content {
+            Content::Link(key, value) => Some(format!(
+                r#"<a href="{}"{}>{}</a>"#,
+                link_href(value, options),
+                link_attributes(options),
+                key
+            )),
+            _ => None,
+        })
+        .collect();
+
+    format!(
+        r#"<p style="margin-left: {}px" class="compact-links">{}</p>"#,
+        links[0].indent.saturating_sub(base) * 20,
+        anchors.join(" · ")
+    )
+}
+
+// 生成嵌套列表形式的正文，每个节点是一个 li，有子节点时在 li 里再嵌套一个 ul
+fn list_body<'a>(lines: impl IntoIterator<Item = &'a Line>, options: &HtmlOptions, hooks: &HtmlHooks) -> String {
+    let mut html = String::from("<ul>");
+
+    // 还没有关闭的 li：缩进、domain 名字、是否已经打开了子列表
+    let mut stack: Vec<(usize, Option<&str>, bool)> = Vec::new();
+
+    for line in lines {
+        while stack.last().is_some_and(|&(indent, _, _)| indent >= line.indent) {
+            let (_, _, nested) = stack.pop().unwrap();
+            if nested {
+                html.push_str("</ul>");
+            }
+            html.push_str("</li>");
+        }
+
+        // 父节点的第一个子节点，先打开子列表
+        if let Some((_, _, nested)) = stack.last_mut()
+            && !*nested
+        {
+            *nested = true;
+            html.push_str("<ul>");
+        }
+
+        let ancestors: Vec<(usize, Option<&str>)> = stack.iter().map(|&(indent, name, _)| (indent, name)).collect();
+        let extra = node_extra(line, &ancestors, options);
+
+        // 间隔放在 li 里面，ul 中只能直接放 li
+        let spacer = spacer(line);
+
+        html.push_str(&match (&line.content, hook(line, hooks)) {
+            (_, Some(hook)) => format!("<li{}>{}{}", extra, spacer, hook(line)),
+            (Content::Atom(atom), None) if options.headings => {
+                // 顶层为 h1，最深到 h6
+                let level = (stack.len() + 1).min(6);
+                format!("<li{}>{}<h{}>{}</h{}>", extra, spacer, level, atom, level)
+            },
+            (Content::Atom(atom), None) => format!("<li{}>{}{}", extra, spacer, atom),
+            (Content::Link(key, value), None) => format!(
+                r#"<li{}>{}<a href="{}"{}>{}</a>"#,
+                extra,
+                spacer,
+                link_href(value, options),
+                link_attributes(options),
+                key
+            ),
+            (Content::Domain(domain), None) => format!("<li{}>{}{}<strong>+ {}</strong>", extra, spacer, icon(domain, options), domain),
+            (Content::Reference(name, target), None) => format!("<li{}>{}{} → <em>{}</em>", extra, spacer, name, target),
+            (Content::Elided(count), None) => format!(r#"<li{} title="{} more">{}…"#, extra, count, spacer),
+        });
+
+        stack.push((line.indent, match &line.content {
+            Content::Domain(name) => Some(name),
+            _ => None,
+        }, false));
+    }
+
+    while let Some((_, _, nested)) = stack.pop() {
+        if nested {
+            html.push_str("</ul>");
+        }
+        html.push_str("</li>");
+    }
+
+    html.push_str("</ul>");
+    html
+}
+
+// 生成按 section 嵌套的正文，domain 的名字作为标题，其它行放在所在的 section 里，
+// 以所在 section 的 domain 的下一级作为最左边的缩进
+fn sections_body<'a>(lines: impl IntoIterator<Item = &'a Line>, base: usize, options: &HtmlOptions, hooks: &HtmlHooks) -> String {
+    let mut html = String::new();
+
+    // 祖先的缩进和 domain 名字，以及还没有关闭的 section 的缩进
+    let mut stack: Vec<(usize, Option<&str>)> = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+
+    for line in lines {
+        while stack.last().is_some_and(|&(indent, _)| indent >= line.indent) {
+            stack.pop();
+        }
+        while open.last().is_some_and(|&indent| indent >= line.indent) {
+            open.pop();
+            html.push_str("</section>");
+        }
+
+        let extra = node_extra(line, &stack, options);
+        html.push_str(&spacer(line));
+
+        match (&line.content, hook(line, hooks)) {
+            (Content::Domain(_), Some(hook)) => {
+                html.push_str("<section>");
+                html.push_str(&hook(line));
+                open.push(line.indent);
+            },
+            (Content::Domain(domain), None) => {
+                // 顶层为 h2，最深到 h6
+                let level = (open.len() + 2).min(6);
+
+                html.push_str(&format!(
+                    "<section><h{}{}>{}{}</h{}>",
+                    level,
+                    extra,
+                    icon(domain, options),
+                    domain,
+                    level
+                ));
+                open.push(line.indent);
+            },
+            (_, Some(hook)) => html.push_str(&hook(line)),
+            (_, None) => {
+                let base = open.last().map_or(base, |&indent| indent + 1);
+                html.push_str(&line_to_html(line, base, options, &extra));
+            },
+        }
+
+        stack.push((line.indent, match &line.content {
+            Content::Domain(name) => Some(name),
+            _ => None,
+        }));
+    }
+
+    for _ in open {
+        html.push_str("</section>");
+    }
+
+    html
+}
+
+// 标签上附加的属性：开启时的 data-kind、data-depth、data-path，以及置顶的 class
+fn node_extra(line: &Line, ancestors: &[(usize, Option<&str>)], options: &HtmlOptions) -> String {
+    let mut extra = match options.data_attributes {
+        true => data_attributes(line, ancestors),
+        false => String::new(),
+    };
+    if line.pinned {
+        extra.push_str(r#" class="pinned""#);
+    }
+    extra
+}
+
+// 这一行种类对应的回调
+fn hook<'h>(line: &Line, hooks: &'h HtmlHooks) -> Option<&'h RenderHook> {
+    match line.content {
+        Content::Atom(_) => hooks.atom.as_ref(),
+        Content::Link(..) => hooks.link.as_ref(),
+        Content::Domain(_) => hooks.domain.as_ref(),
+        Content::Reference(..) | Content::Elided(_) => None,
+    }
+}
+
+// 保留下来的空行显示为间隔
+fn spacer(line: &Line) -> String {
+    match line.blank_before {
+        0 => String::new(),
+        blanks => format!(r#"<div class="spacer" style="height: {}rem"></div>"#, blanks),
+    }
+}
+
+// 以行为单位的转换，extra 是附加在标签上的属性
+fn line_to_html(line: &Line, base: usize, options: &HtmlOptions, extra: &str) -> String {
     // 缩进参数
-    let margin_left = line.indent * 20;
+    let margin_left = line.indent.saturating_sub(base) * 20;
 
     // 构建返回标签
     match &line.content {
         // 原子
+        Content::Atom(atom) if options.headings => {
+            // 顶层为 h1，最深到 h6
+            let level = (line.indent.saturating_sub(base) + 1).min(6);
+
+            format!(
+                r#"<h{} style="margin-left: {}px"{}>{}</h{}>"#,
+                level,
+                margin_left,
+                extra,
+                atom,
+                level
+            )
+        },
+
         Content::Atom(atom) => {
             format!(
-                r#"<p style="margin-left: {}px">{}</p>"#,
+                r#"<p style="margin-left: {}px"{}>{}</p>"#,
                 margin_left,
+                extra,
                 atom
             )
         },
@@ -91,20 +761,832 @@ fn line_to_html(line: &Line) -> String {
         // 链接
         Content::Link(key, value) => {
             format!(
-                r#"<p style="margin-left: {}px"><a href="{}" target="_blank">{}</a></p>"#,
+                r#"<p style="margin-left: {}px"{}><a href="{}"{}>{}</a></p>"#,
                 margin_left,
-                value,
+                extra,
+                link_href(value, options),
+                link_attributes(options),
                 key
             )
         },
 
         // 领域
+        Content::Domain(domain) if options.accessible => {
+            // domain 作为标题，层级跟随深度
+            let level = (line.indent.saturating_sub(base) + 1).min(6);
+
+            format!(
+                r#"<p style="margin-left: {}px"{} role="heading" aria-level="{}" aria-label="{}">{}<strong>+ {}</strong></p>"#,
+                margin_left,
+                extra,
+                level,
+                escape_attribute(domain),
+                icon(domain, options),
+                domain
+            )
+        },
+
         Content::Domain(domain) => {
             format!(
-                r#"<p style="margin-left: {}px"><strong>+ {}</strong></p>"#,
+                r#"<p style="margin-left: {}px"{}>{}<strong>+ {}</strong></p>"#,
                 margin_left,
+                extra,
+                icon(domain, options),
                 domain
             )
+        },
+
+        // 引用
+        Content::Reference(name, target) => {
+            format!(
+                r#"<p style="margin-left: {}px"{}>{} → <em>{}</em></p>"#,
+                margin_left,
+                extra,
+                name,
+                target
+            )
+        },
+
+        // 省略的内容
+        Content::Elided(count) => {
+            format!(
+                r#"<p style="margin-left: {}px"{} title="{} more">…</p>"#,
+                margin_left,
+                extra,
+                count
+            )
+        }
+    }
+}
+
+// domain 的锚点，由父 domain 实际输出的锚点和自己名字的 slug 用 `--` 连接而成，顶层只有 slug
+//
+// slug 中不会出现连续的 `-`，所以 `+ a b` 和 `a` 下面的 `b` 得到不同的锚点；
+// 同一个父 domain 下的同名 domain 仍然相同，输出时要再经过 unique_anchor
+pub fn domain_anchor(parent: Option<&str>, name: &str) -> String {
+    match parent {
+        Some(parent) => format!("{}--{}", parent, slug(name)),
+        None => slug(name),
+    }
+}
+
+// 已经用过的锚点后面依次加上 `-2`、`-3`，直到没有用过为止，并记录下来
+pub fn unique_anchor(anchor: String, used: &mut HashSet<String>) -> String {
+    let mut id = anchor.clone();
+    let mut count = 1;
+    while used.contains(&id) {
+        count += 1;
+        id = format!("{}-{}", anchor, count);
+    }
+    used.insert(id.clone());
+    id
+}
+
+// 面包屑导航，每一级都链接到对应 domain 的锚点，ids 是各级实际输出的锚点
+fn breadcrumb(path: &[&str], ids: &[&str], margin_left: usize) -> String {
+    let crumbs: Vec<String> = path
+        .iter()
+        .zip(ids)
+        .map(|(name, id)| format!(r##"<a href="#{}">{}</a>"##, id, name))
+        .collect();
+
+    format!(
+        r#"<nav class="breadcrumb" style="margin-left: {}px">{}</nav>"#,
+        margin_left,
+        crumbs.join(" / ")
+    )
+}
+
+// domain 名字前面的图标，没有设置时为空
+fn icon(domain: &str, options: &HtmlOptions) -> String {
+    match options.icons.get(domain) {
+        Some(icon) => format!(r#"<span class="icon">{}</span> "#, icon),
+        None => String::new(),
+    }
+}
+
+// data-kind、data-depth 和 data-path 属性，path 是各级祖先 domain 的名字，用 `/` 连接
+fn data_attributes(line: &Line, ancestors: &[(usize, Option<&str>)]) -> String {
+    let path: Vec<&str> = ancestors.iter().filter_map(|&(_, name)| name).collect();
+
+    format!(
+        r#" data-kind="{}" data-depth="{}" data-path="{}""#,
+        line.content.kind_name(),
+        ancestors.len(),
+        escape_attribute(&path.join("/"))
+    )
+}
+
+// 链接值在 href 中的形式
+fn link_href(value: &str, options: &HtmlOptions) -> String {
+    let mut href = value.to_string();
+
+    // 先接上 base，设置了 base 时没有协议的值都按相对链接处理，`guide.html` 不会被当成域名
+    if let Some(base) = &options.base_url {
+        href = with_base_url(&href, base);
+    }
+
+    if let Some(scheme) = &options.default_scheme {
+        href = with_default_scheme(&href, scheme);
+    }
+
+    encode_href(&href)
+}
+
+// 在相对链接前面加上 base，有协议的链接、`//` 开头的链接和只有 `#` 片段的链接保持不变
+pub fn with_base_url(value: &str, base: &str) -> String {
+    if has_scheme(value) || value.starts_with("//") || value.starts_with('#') {
+        return value.to_string();
+    }
+
+    let path = value.strip_prefix("./").unwrap_or(value).trim_start_matches('/');
+    format!("{}/{}", base.trim_end_matches('/'), path)
+}
+
+// 链接值的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Web, // http 或 https
+    File, // file:// 或者看起来像路径
+    Email, // mailto: 或者 `x@y`
+    Other,
+}
+
+impl LinkKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LinkKind::Web => "web",
+            LinkKind::File => "file",
+            LinkKind::Email => "email",
+            LinkKind::Other => "other",
         }
     }
 }
+
+// 根据链接值判断它的种类，可以用来给不同种类的链接加上不同的样式
+pub fn classify_link(value: &str) -> LinkKind {
+    let value = value.trim();
+    let lower = value.to_lowercase();
+
+    // `//` 开头是省略了协议的网址
+    if lower.starts_with("http://") || lower.starts_with("https://") || value.starts_with("//") {
+        return LinkKind::Web;
+    }
+
+    if lower.starts_with("mailto:") {
+        return LinkKind::Email;
+    }
+
+    // `C:\` 或 `C:/` 这样的盘符
+    let drive = value.len() > 2
+        && value.as_bytes()[0].is_ascii_alphabetic()
+        && value.as_bytes()[1] == b':'
+        && matches!(value.as_bytes()[2], b'\\' | b'/');
+
+    if lower.starts_with("file://") || drive || value.starts_with(['/', '\\', '~']) || value.starts_with("./") || value.starts_with("../") {
+        return LinkKind::File;
+    }
+
+    // 没有协议，`@` 两边都不为空
+    if !has_scheme(value)
+        && !value.contains(char::is_whitespace)
+        && value.split_once('@').is_some_and(|(user, host)| !user.is_empty() && !user.contains('/') && host.contains('.'))
+    {
+        return LinkKind::Email;
+    }
+
+    LinkKind::Other
+}
+
+// 是否以 `https:`、`mailto:` 这样的协议开头，`example.com:8080` 中 `:` 前面有 `.`，不算协议
+fn has_scheme(value: &str) -> bool {
+    value.contains("://") || value.split_once(':').is_some_and(|(before, _)| !before.contains('.') && !before.contains('/'))
+}
+
+// 常见的文件扩展名，`index.html` 这样的值是相对路径而不是域名
+const FILE_EXTENSIONS: [&str; 18] = [
+    "html", "htm", "lore", "md", "txt", "pdf", "png", "jpg", "jpeg", "gif", "svg", "css", "js", "json", "xml", "zip", "php", "asp",
+];
+
+// 给看起来像裸域名的链接值加上协议，已经有协议的链接、邮箱地址和相对路径保持不变
+//
+// 主机名的最后一段必须全是字母，并且不是常见的文件扩展名
+pub fn with_default_scheme(value: &str, scheme: &str) -> String {
+    // 第一个 `/` 之前的部分，必须像是一个主机名
+    let host = value.split(['/', '?', '#']).next().unwrap_or("");
+    let suffix = host.rsplit_once('.').map_or("", |(_, suffix)| suffix);
+
+    let bare_domain = !has_scheme(value)
+        && !value.starts_with(['.', '/', '#', '?'])
+        && classify_link(value) != LinkKind::Email
+        && host.contains('.')
+        && !host.contains(char::is_whitespace)
+        && suffix.chars().count() >= 2
+        && suffix.chars().all(char::is_alphabetic)
+        && !FILE_EXTENSIONS.contains(&suffix.to_lowercase().as_str());
+
+    match bare_domain {
+        true => format!("{}://{}", scheme, value),
+        false => value.to_string(),
+    }
+}
+
+// 链接的 target 和 rel 属性
+fn link_attributes(options: &HtmlOptions) -> String {
+    let mut attributes = String::new();
+
+    if options.link_target == LinkTarget::Blank {
+        attributes.push_str(r#" target="_blank""#);
+    }
+
+    if let Some(rel) = &options.link_rel {
+        attributes.push_str(&format!(r#" rel="{}""#, rel));
+    }
+
+    attributes
+}
+
+// 转义放进属性值中的文本
+fn escape_attribute(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// 把名字转换成可以作为锚点 id 的形式：字母和数字转为小写，其它连续的字符合并为一个 `-`
+pub fn slug(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+
+    for c in name.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.trim_end_matches('-').to_string()
+}
+
+// 对放进 href 的链接值做百分号编码
+//
+// 只编码在属性里不合法或会破坏链接的字符，已有的 `%XX` 和 `?`、`&`、`#` 等保持不变，
+// 所以已经编码过的链接不会被重复编码
+pub fn encode_href(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            c if c.is_ascii_control() => encoded.push_str(&format!("%{:02X}", c as u32)),
+            ' ' | '"' | '<' | '>' | '\\' | '^' | '`' | '{' | '|' | '}' => {
+                encoded.push_str(&format!("%{:02X}", c as u32))
+            },
+            c => encoded.push(c),
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_lore::{parse_root, parse_with_options};
+    use crate::parser::ParseOptions;
+    use crate::validate::validate_html_nesting;
+
+    #[test]
+    fn title_from_front_matter_is_escaped() {
+        let root = parse_root("---\ntitle: </title><script>x</script>\n---\na", &ParseOptions::default());
+        let html = to_html(root.title().unwrap(), &root.lines, &HtmlOptions::default());
+
+        assert!(html.contains("<title>&lt;/title&gt;&lt;script&gt;x&lt;/script&gt;</title>"));
+        assert!(!html.contains("<script>x"));
+    }
+
+    #[test]
+    fn chosen_theme_is_embedded_in_a_style_tag() {
+        for theme in [Theme::Light, Theme::Dark, Theme::Compact] {
+            let options = HtmlOptions {
+                theme,
+                ..HtmlOptions::default()
+            };
+            let html = to_html("t", &[], &options);
+
+            assert!(html.contains(&format!(r#"<body class="theme-{}">"#, theme.name())));
+            assert!(html.contains(&format!("<style>\n{}\n</style>", theme.css())));
+            assert!(!html.contains("<styles>"));
+        }
+    }
+
+    #[test]
+    fn theme_is_chosen_by_name() {
+        assert_eq!(Theme::from_name(" Dark "), Some(Theme::Dark));
+        assert_eq!(Theme::from_name("solarized"), None);
+    }
+
+    #[test]
+    fn spaces_in_links_are_encoded() {
+        assert_eq!(encode_href("file:///my notes/a b.html"), "file:///my%20notes/a%20b.html");
+
+        let html = to_html("t", &parse_with_options("notes = ./my notes.html", &ParseOptions::default()), &HtmlOptions::default());
+        assert!(html.contains(r#"href="./my%20notes.html""#));
+    }
+
+    #[test]
+    fn query_strings_are_not_encoded_twice() {
+        let url = "https://example.com/search?q=a%20b&lang=zh#top";
+
+        assert_eq!(encode_href(url), url);
+        assert_eq!(encode_href(&encode_href("a b")), "a%20b");
+    }
+
+    #[test]
+    fn subtree_page_leaves_out_other_domains() {
+        let lines = parse_with_options("+ tools\n  + editors\n    vim = https://vim.org\n+ music\n  radio = https://radio.example", &ParseOptions::default());
+        let html = to_html_subtree(&lines, &["tools", "editors"], "editors", &HtmlOptions::default()).unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.ends_with("</html>"));
+        assert!(html.contains("+ editors"));
+        assert!(html.contains("https://vim.org"));
+        assert!(!html.contains("tools"));
+        assert!(!html.contains("music"));
+        assert!(!html.contains("radio"));
+
+        assert!(to_html_subtree(&lines, &["editors"], "editors", &HtmlOptions::default()).is_none());
+    }
+
+    #[test]
+    fn links_open_in_a_new_tab_with_rel_by_default() {
+        let lines = parse_with_options("a = https://a.example", &ParseOptions::default());
+        let html = to_html("t", &lines, &HtmlOptions::default());
+
+        assert!(html.contains(r#"<a href="https://a.example" target="_blank" rel="noopener noreferrer">a</a>"#));
+    }
+
+    #[test]
+    fn same_window_links_have_no_target() {
+        let lines = parse_with_options("a = https://a.example", &ParseOptions::default());
+        let options = HtmlOptions {
+            link_target: LinkTarget::Same,
+            link_rel: None,
+            ..HtmlOptions::default()
+        };
+        let html = to_html("t", &lines, &options);
+
+        assert!(html.contains(r#"<a href="https://a.example">a</a>"#));
+        assert!(!html.contains("target="));
+    }
+
+    // 检查成对的标签是否正确嵌套，只看正文中会用到的容器标签
+    #[test]
+    fn empty_domains_produce_balanced_tags() {
+        let lines = parse_with_options("+ empty\n+ outer\n  + inner\n+ last", &ParseOptions::default());
+
+        for layout in [Layout::Paragraphs, Layout::List, Layout::Sections] {
+            let options = HtmlOptions {
+                layout,
+                accessible: true,
+                ..HtmlOptions::default()
+            };
+            assert_eq!(validate_html_nesting(&to_html("t", &lines, &options)), Ok(()));
+        }
+    }
+
+    #[test]
+    fn headings_follow_depth_and_stop_at_h6() {
+        let lines = parse_with_options("top\n  second\n    a\n      b\n        c\n          d\n            seventh\nx = y", &ParseOptions::default());
+        let options = HtmlOptions {
+            headings: true,
+            ..HtmlOptions::default()
+        };
+        let html = to_html("t", &lines, &options);
+
+        assert!(html.contains(r#"<h1 style="margin-left: 0px">top</h1>"#));
+        assert!(html.contains(r#"<h2 style="margin-left: 20px">second</h2>"#));
+        assert!(html.contains(r#"<h6 style="margin-left: 120px">seventh</h6>"#));
+        assert!(!html.contains("<h7"));
+
+        // link 仍然是段落
+        assert!(html.contains(r#"<p style="margin-left: 0px"><a href="y""#));
+    }
+
+    #[test]
+    fn custom_link_renderer_replaces_the_default() {
+        let lines = parse_with_options("+ code\n  lore = https://github.com/FleetingLore/lore\n  note", &ParseOptions::default());
+        let hooks = HtmlHooks {
+            link: Some(Box::new(|line: &Line| match &line.content {
+                Content::Link(key, value) => format!(r#"<span class="badge" data-url="{}">{}</span>"#, value, key),
+                _ => String::new(),
+            })),
+            ..HtmlHooks::default()
+        };
+        let html = to_html_with("t", &lines, &HtmlOptions::default(), &hooks);
+
+        assert!(html.contains(r#"<span class="badge" data-url="https://github.com/FleetingLore/lore">lore</span>"#));
+        assert!(!html.contains("<a href"));
+
+        // 其它种类仍然使用默认渲染
+        assert!(html.contains(r#"<p style="margin-left: 20px">note</p>"#));
+        assert!(html.contains("<strong>+ code</strong>"));
+    }
+
+    #[test]
+    fn accessible_mode_adds_landmark_and_aria_headings() {
+        let lines = parse_with_options("+ tools\n  + editors\n    vim = https://vim.org", &ParseOptions::default());
+        let options = HtmlOptions {
+            accessible: true,
+            ..HtmlOptions::default()
+        };
+        let html = to_html("my collection", &lines, &options);
+
+        assert!(html.contains(r#"<main aria-label="my collection">"#));
+        assert!(html.contains("</main>"));
+        assert!(html.contains(r#"role="heading" aria-level="1" aria-label="tools""#));
+        assert!(html.contains(r#"role="heading" aria-level="2" aria-label="editors""#));
+
+        let plain = to_html("my collection", &lines, &HtmlOptions::default());
+        assert!(!plain.contains("aria-"));
+        assert!(!plain.contains("<main"));
+    }
+
+    #[test]
+    fn diff_marks_added_and_removed_nodes() {
+        let old = parse_with_options("+ links\n  a = https://a.example\n  b = https://b.example", &ParseOptions::default());
+        let new = parse_with_options("+ links\n  a = https://a.example\n  c = https://c.example", &ParseOptions::default());
+        let html = to_html_diff("t", &old, &new, &HtmlOptions::default());
+
+        let removed = html.find(r#"<div class="removed""#).unwrap();
+        let added = html.find(r#"<div class="added""#).unwrap();
+        assert_eq!(html.matches(r#"class="removed""#).count(), 1);
+        assert_eq!(html.matches(r#"class="added""#).count(), 1);
+
+        // 标记包住的正是变化的那一行
+        assert!(html[removed..added].contains(">b</a>"));
+        assert!(html[added..].contains(">c</a>"));
+        assert!(!html[..removed].contains("class=\"added\""));
+        assert!(html[..removed].contains(">a</a>"));
+    }
+
+    #[test]
+    fn data_attributes_describe_a_nested_link() {
+        let lines = parse_with_options("+ a\n  + b \"c\"\n    x = https://x.example", &ParseOptions::default());
+        for layout in [Layout::Paragraphs, Layout::List] {
+            let options = HtmlOptions {
+                layout,
+                data_attributes: true,
+                ..HtmlOptions::default()
+            };
+            let html = to_html("t", &lines, &options);
+
+            assert!(html.contains(r#" data-kind="link" data-depth="2" data-path="a/b &quot;c&quot;""#));
+            assert!(html.contains(r#" data-kind="domain" data-depth="0" data-path="""#));
+        }
+        assert!(!to_html("t", &lines, &HtmlOptions::default()).contains("data-"));
+    }
+
+    #[test]
+    fn preserved_blank_lines_become_spacers() {
+        let input = "+ a\n  x = 1\n\n\n  y = 2";
+        let options = ParseOptions {
+            keep_blank_lines: true,
+            ..ParseOptions::default()
+        };
+        let html = to_html("t", &parse_with_options(input, &options), &HtmlOptions::default());
+
+        assert_eq!(html.matches(r#"class="spacer""#).count(), 1);
+        assert!(html.contains(r#"<div class="spacer" style="height: 2rem"></div><p style="margin-left: 20px"><a href="2""#));
+
+        let collapsed = to_html("t", &parse_with_options(input, &ParseOptions::default()), &HtmlOptions::default());
+        assert!(!collapsed.contains("spacer"));
+    }
+
+    #[test]
+    fn list_layout_nests_by_depth() {
+        let lines = parse_with_options("+ a\n  + b\n    x = y\n  note\n+ c", &ParseOptions::default());
+        let options = HtmlOptions {
+            layout: Layout::List,
+            link_rel: None,
+            ..HtmlOptions::default()
+        };
+        let html = to_html("t", &lines, &options);
+
+        assert!(html.contains(concat!(
+            "<ul>",
+            "<li><strong>+ a</strong><ul>",
+            "<li><strong>+ b</strong><ul>",
+            r#"<li><a href="y" target="_blank">x</a></li>"#,
+            "</ul></li>",
+            "<li>note</li>",
+            "</ul></li>",
+            "<li><strong>+ c</strong></li>",
+            "</ul>",
+        )));
+        assert_eq!(validate_html_nesting(&html), Ok(()));
+    }
+
+    #[test]
+    fn list_layout_keeps_headings_spacers_and_hooks() {
+        let lines = parse_with_options("+ a\n  x\n\n  y = 1", &ParseOptions {
+            keep_blank_lines: true,
+            ..ParseOptions::default()
+        });
+        let options = HtmlOptions {
+            layout: Layout::List,
+            headings: true,
+            ..HtmlOptions::default()
+        };
+        let hooks = HtmlHooks {
+            link: Some(Box::new(|line: &Line| format!("<mark>{}</mark>", line.content))),
+            ..HtmlHooks::default()
+        };
+        let html = to_html_with("t", &lines, &options, &hooks);
+
+        assert!(html.contains(concat!(
+            "<li><strong>+ a</strong><ul>",
+            "<li><h2>x</h2></li>",
+            r#"<li><div class="spacer" style="height: 1rem"></div><mark>y = 1</mark></li>"#,
+            "</ul></li>",
+        )));
+        assert_eq!(validate_html_nesting(&html), Ok(()));
+    }
+
+    #[test]
+    fn deep_domains_show_a_breadcrumb_to_each_ancestor() {
+        let lines = parse_with_options("+ Home\n  + Tools\n    + Editors\n      vim = https://vim.org", &ParseOptions::default());
+        let options = HtmlOptions {
+            breadcrumbs: true,
+            ..HtmlOptions::default()
+        };
+        let html = to_html("t", &lines, &options);
+
+        assert!(html.contains(r#"<p style="margin-left: 40px" id="home--tools--editors"><strong>+ Editors</strong></p>"#));
+        assert!(html.contains(concat!(
+            r#"<nav class="breadcrumb" style="margin-left: 60px">"#,
+            r##"<a href="#home">Home</a> / <a href="#home--tools">Tools</a> / <a href="#home--tools--editors">Editors</a>"##,
+            "</nav>",
+        )));
+        assert_eq!(html.matches("<nav").count(), 3);
+    }
+
+    #[test]
+    fn repeated_domain_paths_get_distinct_anchors() {
+        let lines = parse_with_options("+ a b\n+ a\n  + b\n+ t\n  + x\n+ t\n  + x", &ParseOptions::default());
+        let options = HtmlOptions {
+            breadcrumbs: true,
+            ..HtmlOptions::default()
+        };
+        let html = to_html("t", &lines, &options);
+
+        for id in ["a-b", "a", "a--b", "t", "t--x", "t-2", "t-2--x"] {
+            assert_eq!(html.matches(&format!(r#" id="{}""#, id)).count(), 1, "{}", id);
+        }
+
+        // 面包屑指向实际输出的锚点
+        assert!(html.contains(r##"<a href="#t-2">t</a> / <a href="#t-2--x">x</a>"##));
+    }
+
+    #[test]
+    fn bare_domains_get_the_default_scheme() {
+        assert_eq!(with_default_scheme("example.com", "https"), "https://example.com");
+        assert_eq!(with_default_scheme("www.zhihu.com/question/1?a=b", "https"), "https://www.zhihu.com/question/1?a=b");
+        assert_eq!(with_default_scheme("例子.中国", "https"), "https://例子.中国");
+    }
+
+    #[test]
+    fn absolute_urls_keep_their_scheme() {
+        assert_eq!(with_default_scheme("https://x", "https"), "https://x");
+        assert_eq!(with_default_scheme("mailto:me@example.com", "https"), "mailto:me@example.com");
+        assert_eq!(with_default_scheme("//cdn.example.com/a.js", "https"), "//cdn.example.com/a.js");
+    }
+
+    #[test]
+    fn relative_paths_and_emails_are_not_domains() {
+        for value in ["./local.html", "../up.html", "/root.html", "#top", "index.html", "notes/readme.md", "me@example.com", "v1.2", "a b.com"] {
+            assert_eq!(with_default_scheme(value, "https"), value);
+        }
+    }
+
+    #[test]
+    fn default_scheme_applies_to_rendered_links() {
+        let lines = parse_with_options("a = example.com\nb = index.html", &ParseOptions::default());
+        let options = HtmlOptions {
+            default_scheme: Some("https".to_string()),
+            ..HtmlOptions::default()
+        };
+        let html = to_html("t", &lines, &options);
+
+        assert!(html.contains(r#"href="https://example.com""#));
+        assert!(html.contains(r#"href="index.html""#));
+    }
+
+    #[test]
+    fn base_url_prefixes_relative_links_only() {
+        assert_eq!(with_base_url("guide.html", "https://site.org/docs/"), "https://site.org/docs/guide.html");
+        assert_eq!(with_base_url("./a/b.html", "https://site.org"), "https://site.org/a/b.html");
+        assert_eq!(with_base_url("https://x.org/a", "https://site.org"), "https://x.org/a");
+        assert_eq!(with_base_url("#top", "https://site.org"), "#top");
+    }
+
+    #[test]
+    fn base_url_applies_before_default_scheme() {
+        let lines = parse_with_options("+ docs\n  guide = guide.html\n  other = https://x.org", &ParseOptions::default());
+        let options = HtmlOptions {
+            base_url: Some("https://site.org".to_string()),
+            default_scheme: Some("https".to_string()),
+            ..HtmlOptions::default()
+        };
+        let html = to_html("t", &lines, &options);
+
+        assert!(html.contains(r#"href="https://site.org/guide.html""#));
+        assert!(html.contains(r#"href="https://x.org""#));
+        assert!(!html.contains("https://guide.html"));
+    }
+
+    #[test]
+    fn domains_only_map_drops_leaf_content() {
+        let lines = parse_with_options("+ tools\n  vim = https://vim.org\n  + empty\n  note text\n+ news\nguide > tools", &ParseOptions::default());
+        let html = to_html_domains_only("map", &lines, &HtmlOptions::default());
+
+        for name in ["tools", "empty", "news"] {
+            assert!(html.contains(name));
+        }
+        for leaf in ["vim.org", "note text", "guide"] {
+            assert!(!html.contains(leaf));
+        }
+        assert_eq!(validate_html_nesting(&html), Ok(()));
+    }
+
+    #[test]
+    fn size_estimate_is_close_to_the_real_output() {
+        let input = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/../test/test.lore")).unwrap();
+        let lines = parse_with_options(&input, &ParseOptions::default());
+
+        let estimate = estimate_html_bytes(&lines);
+        let actual = to_html("test", &lines, &HtmlOptions::default()).len();
+        assert!(estimate * 2 > actual && estimate < actual * 2, "estimate {}, actual {}", estimate, actual);
+    }
+
+    #[test]
+    fn icons_appear_only_on_their_domain() {
+        let lines = parse_with_options("+ tools\n  vim = https://vim.org\n+ news", &ParseOptions::default());
+        let options = HtmlOptions {
+            icons: HashMap::from([("tools".to_string(), "🔧".to_string())]),
+            ..HtmlOptions::default()
+        };
+        let html = to_html("t", &lines, &options);
+
+        assert!(html.contains(r#"<span class="icon">🔧</span> <strong>+ tools</strong>"#));
+        assert!(html.contains("<strong>+ news</strong>"));
+        assert_eq!(html.matches("🔧").count(), 1);
+    }
+
+    #[test]
+    fn compact_links_only_join_link_only_domains() {
+        let lines = parse_with_options("+ tools\n  vim = https://vim.org\n  git = https://git-scm.com\n+ mixed\n  hn = https://news.ycombinator.com\n  note", &ParseOptions::default());
+        let options = HtmlOptions {
+            compact_links: true,
+            ..HtmlOptions::default()
+        };
+        let html = to_html("t", &lines, &options);
+
+        assert!(html.contains(r#"<a href="https://vim.org" target="_blank" rel="noopener noreferrer">vim</a> · <a href="https://git-scm.com""#));
+        assert_eq!(html.matches(r#"class="compact-links""#).count(), 1);
+        assert!(!html.contains(">hn</a> ·"));
+        assert_eq!(validate_html_nesting(&html), Ok(()));
+    }
+
+    #[test]
+    fn json_tree_page_embeds_the_document() {
+        let root = parse_root("+ tools\n  end = </script>", &ParseOptions::default());
+        let html = to_json_tree_page("tree", &root, &HtmlOptions::default());
+
+        assert!(html.contains(r#"<div id="lore-tree"></div>"#));
+        assert!(html.contains(r#"<script type="application/json" id="lore-data">[{"kind":"domain","text":"tools","children":[{"kind":"link","text":"end","value":"<\/script>"}]}]</script>"#));
+        assert!(html.contains("function render(nodes)"));
+    }
+
+    #[test]
+    fn styles_are_inlined_without_external_stylesheets() {
+        let html = to_html("t", &parse_with_options("+ a\n  b = c", &ParseOptions::default()), &HtmlOptions::default());
+
+        assert_eq!(html.matches("<style>").count(), 1);
+        assert!(html.contains(LIGHT_CSS));
+        assert!(!html.contains("<link"));
+        assert!(!html.contains("<styles>"));
+    }
+
+    #[test]
+    fn sections_nest_with_depth_based_headings() {
+        let lines = parse_with_options("+ tools\n  + editors\n    vim = https://vim.org\n  note\n+ news", &ParseOptions::default());
+        let options = HtmlOptions {
+            layout: Layout::Sections,
+            ..HtmlOptions::default()
+        };
+        let html = to_html("t", &lines, &options);
+
+        let start = html.find("<section><h2>tools</h2>").unwrap();
+        let nested = html.find("<section><h3>editors</h3>").unwrap();
+        let link = html.find("https://vim.org").unwrap();
+        let close = html[link..].find("</section>").unwrap() + link;
+        let note = html.find("note").unwrap();
+        assert!(start < nested && nested < link && close < note);
+        assert!(html.contains("note</p></section><section><h2>news</h2></section>"));
+        assert_eq!(html.matches("<section>").count(), html.matches("</section>").count());
+        assert!(!html.contains("<details"));
+    }
+
+    #[test]
+    fn sections_keep_depth_attributes_spacers_and_hooks() {
+        let input = "+ a\n  x\n    y\n\n  * + b\n    z = 1";
+        let lines = parse_with_options(input, &ParseOptions {
+            keep_blank_lines: true,
+            ..ParseOptions::default()
+        });
+        let options = HtmlOptions {
+            layout: Layout::Sections,
+            headings: true,
+            data_attributes: true,
+            link_rel: None,
+            ..HtmlOptions::default()
+        };
+        let html = to_html("t", &lines, &options);
+
+        // 缩进从所在 section 的下一级算起
+        assert!(html.contains(concat!(
+            r#"<section><h2 data-kind="domain" data-depth="0" data-path="">a</h2>"#,
+            r#"<h1 style="margin-left: 0px" data-kind="atom" data-depth="1" data-path="a">x</h1>"#,
+            r#"<h2 style="margin-left: 20px" data-kind="atom" data-depth="2" data-path="a">y</h2>"#,
+            r#"<div class="spacer" style="height: 1rem"></div>"#,
+            r#"<section><h3 data-kind="domain" data-depth="1" data-path="a" class="pinned">b</h3>"#,
+            r#"<p style="margin-left: 0px" data-kind="link" data-depth="2" data-path="a/b"><a href="1" target="_blank">z</a></p>"#,
+            "</section></section>",
+        )));
+        assert_eq!(validate_html_nesting(&html), Ok(()));
+
+        let hooks = HtmlHooks {
+            atom: Some(Box::new(|line: &Line| format!("<mark>{}</mark>", line.content))),
+            ..HtmlHooks::default()
+        };
+        let hooked = to_html_with("t", &lines, &options, &hooks);
+        assert!(hooked.contains("<mark>x</mark><mark>y</mark>"));
+    }
+
+    #[test]
+    fn source_view_shows_the_raw_lines() {
+        let source = "+ tools\n  vim   =   https://vim.org\n\n  guide>tools\n  a < b";
+        let html = to_html_source_view("src", source, &HtmlOptions::default());
+
+        assert!(html.contains(r#"<span class="line kind-domain"><span class="line-no">1</span> + tools</span>"#));
+        assert!(html.contains(r#"<span class="line kind-link"><span class="line-no">2</span>   vim   =   https://vim.org</span>"#));
+        assert!(html.contains(r#"<span class="line kind-blank"><span class="line-no">3</span> </span>"#));
+        assert!(html.contains(r#"<span class="line kind-reference"><span class="line-no">4</span>   guide&gt;tools</span>"#));
+        assert!(html.contains(r#"<span class="line kind-atom"><span class="line-no">5</span>   a &lt; b</span>"#));
+    }
+
+    #[test]
+    fn fragment_is_the_subtree_without_page_scaffolding() {
+        let lines = parse_with_options("+ tools\n  + editors\n    vim = https://vim.org\n  git = https://git-scm.com\n+ news", &ParseOptions::default());
+        let fragment = to_html_fragment(&lines, &["tools", "editors"], &HtmlOptions::default()).unwrap();
+
+        assert!(fragment.contains("<strong>+ editors</strong>"));
+        assert!(fragment.contains("https://vim.org"));
+        assert!(!fragment.contains("git-scm.com") && !fragment.contains("news"));
+        for scaffolding in ["<html", "<head", "<body", "<style", "<title"] {
+            assert!(!fragment.contains(scaffolding));
+        }
+        assert!(fragment.contains(r#"margin-left: 0px"><strong>+ editors"#));
+        assert!(to_html_fragment(&lines, &["missing"], &HtmlOptions::default()).is_none());
+    }
+
+    #[test]
+    fn links_are_classified_by_their_value() {
+        for value in ["https://vim.org", "HTTP://x.org/a", "//cdn.example.com/a.js"] {
+            assert_eq!(classify_link(value), LinkKind::Web);
+        }
+        for value in ["file:///etc/hosts", "./notes.lore", "../a", "~/docs", r"C:\Users\me", "/usr/share"] {
+            assert_eq!(classify_link(value), LinkKind::File);
+        }
+        for value in ["mailto:me@example.com", "me@example.com"] {
+            assert_eq!(classify_link(value), LinkKind::Email);
+        }
+        for value in ["example.com", "notes", "a/b@c.d", "me@localhost", "ftp://x"] {
+            assert_eq!(classify_link(value), LinkKind::Other);
+        }
+    }
+
+    #[test]
+    fn side_by_side_puts_each_document_in_its_column() {
+        let old = parse_with_options("+ tools\n  vim = https://vim.org\n  ed = https://ed.org", &ParseOptions::default());
+        let new = parse_with_options("+ tools\n  vim = https://vim.org\n  helix = https://helix-editor.com", &ParseOptions::default());
+        let html = to_html_side_by_side("t", &old, &new, &HtmlOptions::default());
+
+        let rows: Vec<&str> = html.split("<tr>").skip(2).collect();
+        assert_eq!(rows.len(), 4);
+        assert!(rows[1].contains(r#"<td class="old"><p"#) && rows[1].matches(">vim</a>").count() == 2);
+        assert!(rows.iter().any(|row| row.contains(r#"<td class="old" style="background-color: #ffebe9"><p"#) && row.contains(">ed</a>") && row.contains(r#"<td class="new"></td>"#)));
+        assert!(rows.iter().any(|row| row.contains(r#"<td class="old"></td>"#) && row.contains(r#"<td class="new" style="background-color: #e6ffec"><p"#) && row.contains(">helix</a>")));
+    }
+}
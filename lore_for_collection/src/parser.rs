@@ -1,48 +1,366 @@
-use crate::line::{Content, Line};
+use crate::error::ParseError;
+use crate::line::{Content, Line, NodeId, INDENT_UNIT};
 
-pub fn parse_line(line: &str) -> Line {
+// 解析选项
+#[derive(Default)]
+pub struct ParseOptions {
+    pub links_in_domains_only: bool, // 只把 domain 内部的 `=` 行解析为 link，顶层的当作 atom
+    pub no_domains: bool, // 不识别 domain，`+ ` 开头的行也当作 atom 或 link
+    pub keep_blank_lines: bool, // 把空行的数量记录在下一行的 blank_before 上
+    pub trailing_comments: bool, // 把 link 和 atom 行尾 ` # ` 之后的部分作为注释
+    pub preserve_separator: bool, // 记录 link 的 `=` 两边原来的空白，重新输出时保持不变
+    pub meta_domain: bool, // 文档第一行是 `+ @meta` 时，把它下面的 `key = value` 或 `key: value` 作为元数据
+    pub merge_same_name_domains: bool, // 把同名的顶层 domain 合并为一个，见 transform::merge_same_name_domains
+}
+
+// 会失败的解析（try_parse_root 和 parse_checked_with）额外的检查，不会失败的解析没有这些检查
+pub struct CheckOptions {
+    pub allow_empty: bool, // 为 false 时，没有任何内容的文档返回错误
+    pub strict_domains: bool, // domain 行只能有名字，含有 `=` 或 `>` 的 domain 返回错误
+}
+
+impl Default for CheckOptions {
+    fn default() -> CheckOptions {
+        CheckOptions {
+            allow_empty: true,
+            strict_domains: false,
+        }
+    }
+}
+
+// 一种 lore 方言的行内规则，缩进和树的结构对所有方言都一样
+pub trait Dialect {
+    // 如果是 domain，返回它的名字
+    fn domain<'a>(&self, trimmed: &'a str) -> Option<&'a str>;
+
+    // 如果是 link，返回它的键和值
+    fn link<'a>(&self, trimmed: &'a str) -> Option<(&'a str, &'a str)>;
+
+    // 如果是引用，返回它的名字和目标
+    //
+    // 默认在第一个 `>` 处分开，两边都不能为空；含有 `=` 的行已经先被当作 link，
+    // 所以链接值中的 `>` 不会被误认为引用
+    fn reference<'a>(&self, trimmed: &'a str) -> Option<(&'a str, &'a str)> {
+        trimmed
+            .split_once('>')
+            .map(|(name, target)| (name.trim(), target.trim()))
+            .filter(|(name, target)| !name.is_empty() && !target.is_empty())
+    }
+
+    // 解析去掉缩进之后的行内容，依次尝试 domain、link、引用，都不是时为 atom
+    fn content(&self, trimmed: &str) -> Content {
+        if let Some(name) = self.domain(trimmed) {
+            Content::Domain(name.to_string())
+        } else if let Some((key, value)) = self.link(trimmed) {
+            Content::Link(key.to_string(), value.to_string())
+        } else if let Some((name, target)) = self.reference(trimmed) {
+            Content::Reference(name.to_string(), target.to_string())
+        } else {
+            Content::Atom(trimmed.to_string())
+        }
+    }
+}
+
+// 默认的方言：`+ ` 开头是 domain，含有 `=` 是 link
+pub struct DefaultDialect;
+
+impl Dialect for DefaultDialect {
+    fn domain<'a>(&self, trimmed: &'a str) -> Option<&'a str> {
+        if trimmed.starts_with('+') && trimmed.len() > 1 {
+            Some(trimmed[1..].trim())
+        } else {
+            None
+        }
+    }
+
+    fn link<'a>(&self, trimmed: &'a str) -> Option<(&'a str, &'a str)> {
+        // 在第一个 `=` 处分成键和值
+        trimmed
+            .split_once('=')
+            .map(|(before_eq, after_eq)| (before_eq.trim(), after_eq.trim()))
+    }
+}
+
+// 不识别 domain 的方言，其它规则与默认方言相同
+pub(crate) struct NoDomainDialect;
+
+impl Dialect for NoDomainDialect {
+    fn domain<'a>(&self, _trimmed: &'a str) -> Option<&'a str> {
+        None
+    }
+
+    fn link<'a>(&self, trimmed: &'a str) -> Option<(&'a str, &'a str)> {
+        DefaultDialect.link(trimmed)
+    }
+}
+
+// 一种缩进的写法，决定一行的缩进级别和内容从哪里开始
+pub trait IndentModel {
+    // 行的缩进级别
+    fn indent_of(&self, line: &str) -> usize;
+
+    // 去掉缩进之后的内容，默认去掉行首所有的空白
+    fn strip<'a>(&self, line: &'a str) -> &'a str {
+        line.trim_start()
+    }
+
+    // 带检查的解析时缩进是否合法，不合法时返回说明，默认任何缩进都合法
+    fn check(&self, _line: &str) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// 每 width 个字节的行首空白算一级缩进，默认的解析使用 SpaceIndent(INDENT_UNIT)
+pub struct SpaceIndent(pub usize);
+
+impl IndentModel for SpaceIndent {
+    fn indent_of(&self, line: &str) -> usize {
+        (line.len() - line.trim_start().len()) / self.0.max(1)
+    }
+
+    // 缩进必须是 width 个空格的整数倍
+    fn check(&self, line: &str) -> Result<(), String> {
+        match (line.len() - line.trim_start().len()).is_multiple_of(self.0.max(1)) {
+            true => Ok(()),
+            false => Err(format!("indentation is not a multiple of {} spaces", self.0)),
+        }
+    }
+}
+
+// 每个行首的制表符算一级缩进
+pub struct TabIndent;
+
+impl IndentModel for TabIndent {
+    fn indent_of(&self, line: &str) -> usize {
+        line.chars().take_while(|&c| c == '\t').count()
+    }
+}
+
+pub fn parse_line(line_no: usize, line: &str) -> Line {
+    parse_line_with(line_no, line, &DefaultDialect)
+}
+
+// 用指定的方言解析一行
+pub fn parse_line_with(line_no: usize, line: &str, dialect: &impl Dialect) -> Line {
+    parse_line_indented(line_no, line, dialect, &SpaceIndent(INDENT_UNIT))
+}
+
+// 用指定的方言和缩进写法解析一行
+pub fn parse_line_indented(line_no: usize, line: &str, dialect: &impl Dialect, model: &impl IndentModel) -> Line {
     // 移除 line 的缩进然后提取缩进级别数
-    let trimmed = line.trim_start();
+    let trimmed = model.strip(line);
 
     // 计算 line 的缩进数
-    let indent = (line.len() - trimmed.len()) / 2;
-
-    // 解析 domain 节点
-    if trimmed.starts_with('+') && trimmed.len() > 1 {
-        // 生成实例
-        let content = Content::Domain(trimmed[1..].trim().to_string());
-
-        // 返回数据
-        Line {
-            indent,
-            content
-        }
-    } else {
-        // 解析 link 节点
-        if let Some(pos) = trimmed.find('=') {
-            // link 键
-            let before_eq = trimmed[..pos].trim();
-
-            // link 值
-            let after_eq = trimmed[pos + 1..].trim();
-
-            // 生成实例
-            let content = Content::Link(before_eq.to_string(), after_eq.to_string());
-            
-            // 返回数据
-            Line {
-                indent,
-                content
-            }
-        } else {
-            // 生成实例
-            let content = Content::Atom(trimmed.to_string());
-            
-            // 返回数据
-            Line {
-                indent,
-                content
-            }
+    let indent = model.indent_of(line);
+
+    // 生成实例，`* ` 开头的 link 和 domain 是置顶的，atom 保持原样
+    let (pinned, content) = match trimmed.strip_prefix("* ") {
+        Some(rest) => match dialect.content(rest.trim_start()) {
+            content @ (Content::Link(..) | Content::Domain(_)) => (true, content),
+            _ => (false, dialect.content(trimmed)),
+        },
+        None => (false, dialect.content(trimmed)),
+    };
+
+    // 返回数据
+    Line {
+        indent,
+        content,
+        line_no,
+        blank_before: 0,
+        pinned,
+        comment: None,
+        separator: None,
+        id: NodeId::next()
+    }
+}
+
+// 在第一个 ` # ` 处分出行尾注释，链接中的 `#frag` 和转义的 `\#` 前后没有空格，不受影响
+// 前面没有内容的整行注释（比如 `  # TODO: fix`）不切分，仍然整行作为文字
+pub(crate) fn split_comment(line: &str) -> (&str, Option<&str>) {
+    match line.find(" # ") {
+        Some(pos) if !line[..pos].trim().is_empty() => (line[..pos].trim_end(), Some(line[pos + 3..].trim())),
+        _ => (line, None),
+    }
+}
+
+// 带检查的行解析，遇到不合法的写法时返回带位置的错误
+pub fn check_line(line_no: usize, line: &str) -> Result<Line, ParseError> {
+    check_line_indented(line_no, line, &SpaceIndent(INDENT_UNIT))
+}
+
+// 用指定的缩进写法做带检查的行解析
+pub fn check_line_indented(line_no: usize, line: &str, model: &impl IndentModel) -> Result<Line, ParseError> {
+    let trimmed = model.strip(line);
+
+    // 缩进的字节数
+    let spaces = line.len() - trimmed.len();
+
+    if let Err(message) = model.check(line) {
+        return Err(ParseError::new(line_no, column_at(line, spaces), message));
+    }
+
+    let parsed = parse_line_indented(line_no, line, &DefaultDialect, model);
+
+    // link 的键和值都不能为空
+    if let Content::Link(key, value) = &parsed.content {
+        let eq = spaces + trimmed.find('=').unwrap_or(0);
+
+        if key.is_empty() {
+            return Err(ParseError::new(line_no, column_at(line, eq), "link has no name before `=`"));
+        }
+
+        if value.is_empty() {
+            return Err(ParseError::new(line_no, column_at(line, eq), "link has no value after `=`"));
+        }
+    }
+
+    Ok(parsed)
+}
+
+// 宽松解析时需要猜测的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmbiguityKind {
+    OddIndent, // 缩进不是两个空格的整数倍
+    OverIndented, // 比上一行深出一级以上
+    DomainWithoutSpace, // `+` 后面没有空格
+    BareDomainMarker, // 只有一个 `+`
+    MultipleSeparators, // 一行中有多个 `=`
+    EmptyLinkPart, // link 的键或值为空
+}
+
+// 宽松解析时做出的一个假设
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ambiguity {
+    pub line: usize,
+    pub kind: AmbiguityKind,
+    pub assumed: String, // 解析器是怎么理解这一行的
+}
+
+// 找出一行中需要猜测的写法，缩进是否过深需要结合上下文另外判断
+pub fn ambiguities(line_no: usize, line: &str) -> Vec<Ambiguity> {
+    let trimmed = line.trim_start();
+    let spaces = line.len() - trimmed.len();
+
+    let mut found = Vec::new();
+    let mut push = |kind, assumed: String| {
+        found.push(Ambiguity {
+            line: line_no,
+            kind,
+            assumed,
+        })
+    };
+
+    if !spaces.is_multiple_of(INDENT_UNIT) {
+        push(AmbiguityKind::OddIndent, format!("{} spaces read as indent level {}", spaces, spaces / INDENT_UNIT));
+    }
+
+    if trimmed == "+" {
+        push(AmbiguityKind::BareDomainMarker, "read as an atom".to_string());
+    } else if let Some(rest) = trimmed.strip_prefix('+') {
+        if !rest.starts_with(char::is_whitespace) {
+            push(AmbiguityKind::DomainWithoutSpace, format!("read as domain `{}`", rest.trim()));
+        }
+    } else if let Some((key, value)) = trimmed.split_once('=') {
+        if value.contains('=') {
+            push(AmbiguityKind::MultipleSeparators, format!("split at the first `=` into `{}` and `{}`", key.trim(), value.trim()));
         }
+        if key.trim().is_empty() || value.trim().is_empty() {
+            push(AmbiguityKind::EmptyLinkPart, "read as a link with an empty name or value".to_string());
+        }
+    }
+
+    found
+}
+
+// 把字节位置换算成从 1 开始的字符列号
+fn column_at(line: &str, byte: usize) -> usize {
+    line[..byte].chars().count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_lore::parse_with_dialect;
+
+    // `*` 开头是 domain，`:` 分开链接的名字和地址
+    struct StarDialect;
+
+    impl Dialect for StarDialect {
+        fn domain<'a>(&self, trimmed: &'a str) -> Option<&'a str> {
+            trimmed.strip_prefix('*').map(str::trim).filter(|name| !name.is_empty())
+        }
+
+        fn link<'a>(&self, trimmed: &'a str) -> Option<(&'a str, &'a str)> {
+            trimmed
+                .split_once(": ")
+                .map(|(name, value)| (name.trim(), value.trim()))
+        }
+    }
+
+    #[test]
+    fn custom_dialect_parses_its_own_markers() {
+        let lines = parse_with_dialect("* tools\n  vim: https://vim.org\n  a = b\n  guide > tools", &StarDialect);
+        let contents: Vec<&Content> = lines.iter().map(|line| &line.content).collect();
+
+        assert_eq!(
+            contents,
+            vec![
+                &Content::Domain("tools".to_string()),
+                &Content::Link("vim".to_string(), "https://vim.org".to_string()),
+                &Content::Atom("a = b".to_string()),
+                &Content::Reference("guide".to_string(), "tools".to_string()),
+            ]
+        );
+        assert_eq!(lines[1].indent, 1);
+    }
+
+    #[test]
+    fn pinned_links_and_domains() {
+        let link = parse_line(1, "  * zhi_hu = https://www.zhihu.com");
+        assert!(link.pinned);
+        assert_eq!(link.indent, 1);
+        assert_eq!(link.content, Content::Link("zhi_hu".to_string(), "https://www.zhihu.com".to_string()));
+        assert_eq!(link.to_string(), "  * zhi_hu = https://www.zhihu.com");
+
+        let domain = parse_line(1, "* + favourites");
+        assert!(domain.pinned);
+        assert_eq!(domain.content, Content::Domain("favourites".to_string()));
+    }
+
+    #[test]
+    fn atoms_starting_with_a_star_are_not_pinned() {
+        for (line, text) in [("* just a note", "* just a note"), ("*bold*", "*bold*"), ("*+ x", "*+ x")] {
+            let parsed = parse_line(1, line);
+            assert!(!parsed.pinned);
+            assert_eq!(parsed.content, Content::Atom(text.to_string()));
+        }
+
+        // 没有空格时 `*` 是 link 名字的一部分
+        let link = parse_line(1, "*a = b");
+        assert!(!link.pinned);
+        assert_eq!(link.content, Content::Link("*a".to_string(), "b".to_string()));
+    }
+
+    #[test]
+    fn references_need_no_separator() {
+        assert_eq!(parse_line(1, "parent > child").content, Content::Reference("parent".to_string(), "child".to_string()));
+        assert_eq!(parse_line(1, "a>b").content, Content::Reference("a".to_string(), "b".to_string()));
+        assert_eq!(parse_line(1, "a >").content, Content::Atom("a >".to_string()));
+        assert_eq!(parse_line(1, "> b").content, Content::Atom("> b".to_string()));
+    }
+
+    #[test]
+    fn separators_win_over_arrows() {
+        assert_eq!(parse_line(1, "a = b>c").content, Content::Link("a".to_string(), "b>c".to_string()));
+        assert_eq!(parse_line(1, "a > b = c").content, Content::Link("a > b".to_string(), "c".to_string()));
+    }
+
+    #[test]
+    fn checked_lines_follow_the_indent_width() {
+        let error = check_line_indented(1, "  a = b", &SpaceIndent(4)).unwrap_err();
+        assert_eq!((error.column, error.message.as_str()), (3, "indentation is not a multiple of 4 spaces"));
+        assert_eq!(check_line_indented(1, "    a = b", &SpaceIndent(4)).unwrap().indent, 1);
+        assert_eq!(check_line_indented(1, "\t\ta = b", &TabIndent).unwrap().indent, 2);
     }
 }
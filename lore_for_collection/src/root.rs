@@ -0,0 +1,648 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::line::{Content, Line};
+use crate::output::{self, LinkKind};
+use crate::tree;
+
+// 解析后的文档，分为元数据和正文
+#[derive(Debug)]
+pub struct Root {
+    pub metadata: HashMap<String, String>,
+    pub lines: Vec<Line>,
+}
+
+// 遍历时得到的 link，path 是它所在的各级 domain 的名字
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link<'a> {
+    pub name: &'a str,
+    pub value: &'a str,
+    pub path: Vec<&'a str>,
+}
+
+// 一个引用以及它的目标是否存在，目标是文档中某个 domain 的名字
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedReference<'a> {
+    pub name: &'a str,
+    pub target: &'a str,
+    pub line: usize, // 引用所在的行号
+    pub resolved: bool,
+}
+
+// 每个 domain 被引用的次数，名字重复的 domain 无法区分引用指向哪一个，单独列出
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ReferenceCounts {
+    pub counts: HashMap<String, usize>, // 每个 domain 名字被引用的次数，没有被引用的为 0
+    pub ambiguous: Vec<String>, // 出现不止一次的 domain 名字，按第一次出现的顺序排列
+}
+
+impl Root {
+    // 元数据中的标题
+    pub fn title(&self) -> Option<&str> {
+        self.metadata.get("title").map(String::as_str)
+    }
+
+    // 文档内容的哈希，只看结构和内容，不受缩进宽度和多余空白的影响
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv::new();
+
+        // 元数据按键排序后参与哈希
+        let mut metadata: Vec<_> = self.metadata.iter().collect();
+        metadata.sort();
+        for (key, value) in metadata {
+            hasher.write(b"meta");
+            hasher.write(key.as_bytes());
+            hasher.write(value.trim().as_bytes());
+        }
+
+        // 用相对深度代替缩进
+        let mut stack: Vec<usize> = Vec::new();
+        for line in &self.lines {
+            while stack.last().is_some_and(|&indent| indent >= line.indent) {
+                stack.pop();
+            }
+            hasher.write(&stack.len().to_le_bytes());
+            stack.push(line.indent);
+
+            match &line.content {
+                Content::Atom(atom) => {
+                    hasher.write(b"atom");
+                    hasher.write(atom.trim().as_bytes());
+                },
+                Content::Link(key, value) => {
+                    hasher.write(b"link");
+                    hasher.write(key.as_bytes());
+                    hasher.write(value.as_bytes());
+                },
+                Content::Domain(domain) => {
+                    hasher.write(b"domain");
+                    hasher.write(domain.as_bytes());
+                },
+                Content::Reference(name, target) => {
+                    hasher.write(b"reference");
+                    hasher.write(name.as_bytes());
+                    hasher.write(target.as_bytes());
+                },
+                Content::Elided(count) => {
+                    hasher.write(b"elided");
+                    hasher.write(&count.to_le_bytes());
+                },
+            }
+        }
+
+        hasher.finish()
+    }
+
+    // 源文件第 line_no 行（从 1 开始）对应的节点，空行、元数据和被合并掉的 domain 没有对应的节点
+    //
+    // 节点记录的是源文件中的行号，所以被跳过的空行不会让后面的行错位，
+    // 合并同名 domain 之后行号不再有序，所以逐行查找
+    pub fn node_at_line(&self, line_no: usize) -> Option<&Line> {
+        self.lines.iter().find(|line| line.line_no == line_no)
+    }
+
+    // 文档的结构签名，按顺序列出每个节点的深度和种类，不包含任何文本，
+    // 结构相同而内容不同的文档得到相同的签名，比如 `0:domain,1:link,1:link`
+    pub fn shape_signature(&self) -> String {
+        let mut stack: Vec<usize> = Vec::new();
+
+        self.lines
+            .iter()
+            .map(|line| {
+                while stack.last().is_some_and(|&indent| indent >= line.indent) {
+                    stack.pop();
+                }
+                let depth = stack.len();
+                stack.push(line.indent);
+
+                format!("{}:{}", depth, line.content.kind_name())
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    // 满足条件的行数，只遍历一次，不需要建树
+    pub fn count_where(&self, predicate: impl Fn(&Line) -> bool) -> usize {
+        self.lines.iter().filter(|line| predicate(line)).count()
+    }
+
+    // 所有 domain 的名字，去掉重复，按第一次出现的顺序排列
+    pub fn domain_names(&self) -> Vec<&str> {
+        let mut seen = HashSet::new();
+
+        self.lines
+            .iter()
+            .filter_map(|line| match &line.content {
+                Content::Domain(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .filter(|name| seen.insert(*name))
+            .collect()
+    }
+
+    // 每个 domain 的字数，包括所有后代 atom 的文本和 link、引用的名字，按文档顺序排列
+    //
+    // 英文等按空白分词，每个汉字、假名或谚文字符单独算一个词
+    pub fn domain_word_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+
+        // 祖先的缩进，以及是 domain 时它在 counts 中的位置
+        let mut stack: Vec<(usize, Option<usize>)> = Vec::new();
+
+        for line in &self.lines {
+            while stack.last().is_some_and(|&(indent, _)| indent >= line.indent) {
+                stack.pop();
+            }
+
+            let words = match &line.content {
+                Content::Atom(text) | Content::Link(text, _) | Content::Reference(text, _) => word_count(text),
+                Content::Domain(_) | Content::Elided(_) => 0,
+            };
+            for &(_, domain) in &stack {
+                if let Some(domain) = domain {
+                    counts[domain].1 += words;
+                }
+            }
+
+            let domain = match &line.content {
+                Content::Domain(name) => {
+                    counts.push((name.clone(), 0));
+                    Some(counts.len() - 1)
+                },
+                _ => None,
+            };
+            stack.push((line.indent, domain));
+        }
+
+        counts
+    }
+
+    // 按文档顺序给出每个 domain 和它的直接子 link，嵌套的 domain 单独给出，没有 link 的 domain 对应空数组
+    pub fn grouped_links(&self) -> impl Iterator<Item = (&str, Vec<Link<'_>>)> {
+        let parents = tree::parents(&self.lines);
+
+        // 先把 link 分到各自的父节点下，links 与 link 行一一对应
+        let mut groups: Vec<Vec<Link<'_>>> = (0..self.lines.len()).map(|_| Vec::new()).collect();
+        let mut links = self.links();
+        for (index, line) in self.lines.iter().enumerate() {
+            if let Content::Link(..) = line.content
+                && let (Some(link), Some(parent)) = (links.next(), parents[index])
+            {
+                groups[parent].push(link);
+            }
+        }
+
+        self.lines
+            .iter()
+            .zip(groups)
+            .filter_map(|(line, group)| match &line.content {
+                Content::Domain(name) => Some((name.as_str(), group)),
+                _ => None,
+            })
+    }
+
+    // 最长的 link 值的显示宽度，中日韩文字和全角字符算两个宽度，没有 link 时为 0
+    pub fn max_link_value_len(&self) -> usize {
+        self.links().map(|link| display_width(link.value)).max().unwrap_or(0)
+    }
+
+    // 最长的 link 名字的显示宽度
+    pub fn max_link_name_len(&self) -> usize {
+        self.links().map(|link| display_width(link.name)).max().unwrap_or(0)
+    }
+
+    // 按文档顺序遍历所有引用，同时给出目标 domain 是否存在
+    pub fn references(&self) -> impl Iterator<Item = ResolvedReference<'_>> {
+        let domains: HashSet<&str> = self.domain_names().into_iter().collect();
+
+        self.lines.iter().filter_map(move |line| match &line.content {
+            Content::Reference(name, target) => Some(ResolvedReference {
+                name,
+                target,
+                line: line.line_no,
+                resolved: domains.contains(target.as_str()),
+            }),
+            _ => None,
+        })
+    }
+
+    // 每个 domain 被引用的次数，悬空的引用不计
+    pub fn reference_counts(&self) -> ReferenceCounts {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let mut ambiguous = Vec::new();
+
+        for line in &self.lines {
+            if let Content::Domain(name) = &line.content
+                && counts.insert(name.clone(), 0).is_some()
+                && !ambiguous.contains(name)
+            {
+                ambiguous.push(name.clone());
+            }
+        }
+
+        for reference in self.references().filter(|reference| reference.resolved) {
+            *counts.get_mut(reference.target).unwrap() += 1;
+        }
+
+        ReferenceCounts { counts, ambiguous }
+    }
+
+    // 带有指定标记的注释，返回行号和标记之后的文字，比如找出所有的 TODO
+    //
+    // 整行注释 `# TODO: ...` 总能找到，`a = b # TODO: ...` 这样的行尾注释只有用 trailing_comments 解析时才能找到
+    pub fn collect_tagged_comments(&self, tag: &str) -> Vec<(usize, String)> {
+        self.lines
+            .iter()
+            .filter(|line| line.comment_tag() == Some(tag))
+            .filter_map(|line| {
+                let (_, text) = line.comment_text()?.split_once(':')?;
+                Some((line.line_no, text.trim().to_string()))
+            })
+            .collect()
+    }
+
+    // 去掉所有注释，只留下有意义的内容：删除整行注释，清空行尾注释
+    //
+    // 缩进在整行注释下面的行提升到注释所在的层级，挂到注释原来的父节点下。文档中没有占位符，不需要处理
+    pub fn strip_nonsemantic(mut self) -> Root {
+        // 从后往前删除，嵌套的注释先处理，前面的行的位置不受影响
+        for index in (0..self.lines.len()).rev() {
+            if !self.lines[index].is_comment() {
+                continue;
+            }
+
+            let end = tree::subtree_end(&self.lines, index);
+            let base = self.lines[index].indent;
+            let shift = self.lines[index + 1..end].iter().map(|line| line.indent - base).min().unwrap_or(0);
+            for line in &mut self.lines[index + 1..end] {
+                line.indent -= shift;
+            }
+
+            self.lines.remove(index);
+        }
+
+        for line in &mut self.lines {
+            line.comment = None;
+        }
+
+        self
+    }
+
+    // 网页链接的主机名和出现的次数，不是网址的链接不计
+    //
+    // 给出 default_scheme 时，`example.com` 这样没有协议的链接先按 output::with_default_scheme 补上协议
+    pub fn link_hosts(&self, default_scheme: Option<&str>) -> BTreeMap<String, usize> {
+        let mut hosts = BTreeMap::new();
+
+        for link in self.links() {
+            let value = match default_scheme {
+                Some(scheme) => output::with_default_scheme(link.value, scheme),
+                None => link.value.to_string(),
+            };
+
+            if output::classify_link(&value) != LinkKind::Web {
+                continue;
+            }
+
+            // 去掉协议、路径、用户名和端口
+            let rest = value.split_once("//").map_or(value.as_str(), |(_, rest)| rest);
+            let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+            let host = authority.rsplit('@').next().unwrap_or("");
+            let host = host.split(':').next().unwrap_or("").to_lowercase();
+
+            if !host.is_empty() {
+                *hosts.entry(host).or_insert(0) += 1;
+            }
+        }
+
+        hosts
+    }
+
+    // 按文档顺序惰性遍历所有 link
+    pub fn links(&self) -> impl Iterator<Item = Link<'_>> {
+        Links {
+            lines: self.lines.iter(),
+            stack: Vec::new(),
+        }
+    }
+}
+
+// links 的迭代器，一边遍历一边维护祖先栈
+struct Links<'a> {
+    lines: std::slice::Iter<'a, Line>,
+    stack: Vec<(usize, Option<&'a str>)>, // 祖先的缩进，以及 domain 的名字
+}
+
+impl<'a> Iterator for Links<'a> {
+    type Item = Link<'a>;
+
+    fn next(&mut self) -> Option<Link<'a>> {
+        for line in self.lines.by_ref() {
+            // 弹出缩进不浅于当前行的祖先
+            while self.stack.last().is_some_and(|&(indent, _)| indent >= line.indent) {
+                self.stack.pop();
+            }
+
+            match &line.content {
+                Content::Link(name, value) => {
+                    let path = self.stack.iter().filter_map(|&(_, domain)| domain).collect();
+                    self.stack.push((line.indent, None));
+
+                    return Some(Link {
+                        name,
+                        value,
+                        path,
+                    });
+                },
+                Content::Domain(name) => self.stack.push((line.indent, Some(name))),
+                Content::Atom(_) | Content::Reference(..) | Content::Elided(_) => self.stack.push((line.indent, None)),
+            }
+        }
+
+        None
+    }
+}
+
+// 文本的词数，连续的非空白字符算一个词，中日韩文字每个字符算一个词
+fn word_count(text: &str) -> usize {
+    let mut count = 0;
+    let mut in_word = false;
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            count += 1;
+            in_word = false;
+        } else if c.is_whitespace() {
+            in_word = false;
+        } else if !in_word {
+            count += 1;
+            in_word = true;
+        }
+    }
+
+    count
+}
+
+// 中日韩文字，包括汉字、假名和谚文
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30ff // 平假名和片假名
+        | 0x3400..=0x4dbf // 汉字扩展 A
+        | 0x4e00..=0x9fff // 基本汉字
+        | 0xac00..=0xd7af // 谚文音节
+        | 0xf900..=0xfaff // 兼容汉字
+        | 0x20000..=0x2fa1f // 汉字扩展 B 及以后
+    )
+}
+
+// 文本在等宽字体下的显示宽度
+pub(crate) fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match is_cjk(c) || matches!(c, '\u{3000}'..='\u{303f}' | '\u{ff00}'..='\u{ff60}' | '\u{ffe0}'..='\u{ffe6}') {
+            true => 2,
+            false => 1,
+        })
+        .sum()
+}
+
+// FNV-1a 哈希，结果不随 Rust 版本变化
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf29ce484222325)
+    }
+
+    // 每段数据后面加上长度，避免不同的切分得到相同的结果
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes.iter().chain(&(bytes.len() as u64).to_le_bytes()) {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_lore::parse_root;
+    use crate::parser::ParseOptions;
+
+    fn root(input: &str) -> Root {
+        parse_root(input, &ParseOptions::default())
+    }
+
+    #[test]
+    fn links_match_an_eager_walk_over_ancestors() {
+        let root = root("top = t\n+ a\n  x = 1\n  + b\n    note\n      y = 2\n  z = 3\n+ c\n  w = 4");
+
+        // 对每个 link 单独找出祖先 domain
+        let eager: Vec<Link> = root
+            .lines
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| match &line.content {
+                Content::Link(name, value) => Some(Link {
+                    name,
+                    value,
+                    path: tree::ancestors(&root.lines, index)
+                        .into_iter()
+                        .filter_map(|line| match &line.content {
+                            Content::Domain(name) => Some(name.as_str()),
+                            _ => None,
+                        })
+                        .collect(),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(root.links().collect::<Vec<_>>(), eager);
+        assert_eq!(eager[2].path, vec!["a", "b"]);
+        assert_eq!(eager[3].path, vec!["a"]);
+    }
+
+    #[test]
+    fn indent_width_does_not_change_the_hash() {
+        let two = root("+ links\n  a = https://a.example\n  + more\n    b = c");
+        let four = root("+ links\n    a  =  https://a.example\n    + more\n        b = c");
+
+        assert_eq!(two.content_hash(), four.content_hash());
+    }
+
+    #[test]
+    fn content_and_structure_change_the_hash() {
+        let original = root("+ links\n  a = https://a.example\n  b = c");
+
+        assert_ne!(original.content_hash(), root("+ links\n  a = https://b.example\n  b = c").content_hash());
+        assert_ne!(original.content_hash(), root("+ links\n  a = https://a.example\nb = c").content_hash());
+        assert_ne!(original.content_hash(), root("---\ntitle: x\n---\n+ links\n  a = https://a.example\n  b = c").content_hash());
+    }
+
+    #[test]
+    fn domain_names_are_deduplicated_in_first_occurrence_order() {
+        let root = root("+ tools\n  + editors\n    vim = v\n+ music\n  + editors\n+ tools\n  + radio");
+
+        assert_eq!(root.domain_names(), vec!["tools", "editors", "music", "radio"]);
+    }
+
+    #[test]
+    fn source_lines_map_to_nodes_across_blank_lines() {
+        let root = root("---\ntitle: x\n---\n+ links\n\n\n  a = b\n\n  c");
+
+        assert!(root.node_at_line(2).is_none());
+        assert_eq!(root.node_at_line(4).map(|line| &line.content), Some(&Content::Domain("links".to_string())));
+        assert!(root.node_at_line(5).is_none());
+        assert_eq!(root.node_at_line(7).map(|line| &line.content), Some(&Content::Link("a".to_string(), "b".to_string())));
+        assert_eq!(root.node_at_line(9).map(|line| &line.content), Some(&Content::Atom("c".to_string())));
+        assert!(root.node_at_line(10).is_none());
+    }
+
+    #[test]
+    fn source_lines_map_to_nodes_after_merging_domains() {
+        let options = ParseOptions {
+            merge_same_name_domains: true,
+            ..ParseOptions::default()
+        };
+        let root = parse_root("+ a\n  x\n+ b\n  y\n+ a\n  z", &options);
+        let content = |line_no| root.node_at_line(line_no).map(|line| line.content.to_string());
+
+        assert_eq!(content(3), Some("+ b".to_string()));
+        assert_eq!(content(4), Some("y".to_string()));
+        assert_eq!(content(5), None);
+        assert_eq!(content(6), Some("z".to_string()));
+    }
+
+    #[test]
+    fn domain_word_counts_include_nested_text() {
+        let root = root("+ 笔记\n  今天天气很好\n  + reading list\n    two words\n    the rust book = https://doc.rust-lang.org/book\n    guide > 笔记\n+ empty\nloose words here");
+
+        assert_eq!(
+            root.domain_word_counts(),
+            vec![("笔记".to_string(), 6 + 2 + 3 + 1), ("reading list".to_string(), 2 + 3 + 1), ("empty".to_string(), 0)]
+        );
+    }
+
+    #[test]
+    fn links_are_grouped_under_their_domains() {
+        let root = root("+ tools\n  vim = https://vim.org\n  + nested\n    deep = d\n  git = https://git-scm.com\n+ empty\n+ news\n  hn = https://news.ycombinator.com");
+        let groups: Vec<(&str, Vec<(&str, &str)>)> = root
+            .grouped_links()
+            .map(|(name, links)| (name, links.into_iter().map(|link| (link.name, link.value)).collect()))
+            .collect();
+
+        assert_eq!(
+            groups,
+            vec![
+                ("tools", vec![("vim", "https://vim.org"), ("git", "https://git-scm.com")]),
+                ("nested", vec![("deep", "d")]),
+                ("empty", vec![]),
+                ("news", vec![("hn", "https://news.ycombinator.com")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn same_shape_gives_the_same_signature() {
+        let first = root("+ tools\n  vim = https://vim.org\n  + more\n    note");
+        let second = root("+ 新闻\n    hn = https://news.ycombinator.com\n    + 更多\n        别的");
+        let different = root("+ tools\n  vim = https://vim.org\nnote");
+
+        assert_eq!(first.shape_signature(), "0:domain,1:link,1:domain,2:atom");
+        assert_eq!(first.shape_signature(), second.shape_signature());
+        assert_ne!(first.shape_signature(), different.shape_signature());
+    }
+
+    #[test]
+    fn count_where_counts_matching_lines() {
+        let root = root("+ a\n  x = 1\n  + b\n    + c\n      y = 2\n    + d\nz = 3");
+
+        assert_eq!(root.count_where(|line| matches!(line.content, Content::Link(..))), 3);
+        assert_eq!(root.count_where(|line| matches!(line.content, Content::Domain(_)) && line.indent >= 2), 2);
+        assert_eq!(root.count_where(|_| false), 0);
+    }
+
+    #[test]
+    fn longest_link_is_measured_in_display_width() {
+        let links = root("+ tools\n  vim = https://vim.org\n  文档 = 一二三四五六七八\n  abc = 123456789");
+
+        // 八个汉字的显示宽度是 16，比 `https://vim.org` 的 15 宽，按字数或字节数都得不到 16
+        assert_eq!(links.max_link_value_len(), 16);
+        assert_eq!(links.max_link_name_len(), 4);
+        assert_eq!(root("+ empty").max_link_value_len(), 0);
+    }
+
+    #[test]
+    fn references_carry_their_resolution_status() {
+        let root = root("+ docs\n  guide > docs\n  old > gone");
+        let references: Vec<ResolvedReference> = root.references().collect();
+
+        assert_eq!(
+            references,
+            vec![
+                ResolvedReference { name: "guide", target: "docs", line: 2, resolved: true },
+                ResolvedReference { name: "old", target: "gone", line: 3, resolved: false },
+            ]
+        );
+    }
+
+    #[test]
+    fn tagged_comments_are_collected_by_tag() {
+        let input = "# TODO: top level\n+ tools\n  vim = https://vim.org # TODO: pin a version\n  # FIXME: broken link\n  git = https://git-scm.com # just a note\n  # see: elsewhere";
+        let options = ParseOptions {
+            trailing_comments: true,
+            ..ParseOptions::default()
+        };
+        let root = parse_root(input, &options);
+
+        assert_eq!(root.collect_tagged_comments("TODO"), vec![(1, "top level".to_string()), (3, "pin a version".to_string())]);
+        assert_eq!(root.collect_tagged_comments("FIXME"), vec![(4, "broken link".to_string())]);
+        assert_eq!(root.lines[4].comment_text(), Some("just a note"));
+        assert_eq!(root.lines[4].comment_tag(), None);
+        assert_eq!(root.lines[5].comment_tag(), None);
+        assert!(root.collect_tagged_comments("NOTE").is_empty());
+    }
+
+    #[test]
+    fn trailing_tags_need_the_trailing_comments_option() {
+        let root = root("# TODO: top level\n+ tools\n  vim = https://vim.org # TODO: pin a version");
+
+        assert_eq!(root.collect_tagged_comments("TODO"), vec![(1, "top level".to_string())]);
+    }
+
+    #[test]
+    fn link_hosts_count_each_web_host() {
+        let root = root("+ tools\n  a = https://vim.org/download\n  b = http://user@VIM.org:8080/?q=1\n  c = https://git-scm.com\n  d = ./local.html\n  e = me@example.com\n  f = example.com");
+
+        assert_eq!(root.link_hosts(None), BTreeMap::from([("git-scm.com".to_string(), 1), ("vim.org".to_string(), 2)]));
+        assert_eq!(root.link_hosts(Some("https")).get("example.com"), Some(&1));
+        assert_eq!(root.link_hosts(Some("https")).len(), 3);
+    }
+
+    #[test]
+    fn stripping_removes_comments_and_keeps_structure() {
+        let options = ParseOptions {
+            trailing_comments: true,
+            ..ParseOptions::default()
+        };
+        let input = "# TODO: top\n+ tools\n  vim = https://vim.org # editor\n  # grouped\n    git = https://git-scm.com\n    # nested\n      jq = https://jqlang.org\n  note\nguide > tools";
+        let root = parse_root(input, &options);
+        assert_eq!(root.lines.len(), 9);
+
+        let stripped = root.strip_nonsemantic();
+        let rendered: Vec<String> = stripped.lines.iter().map(|line| line.to_string()).collect();
+
+        assert_eq!(rendered, vec!["+ tools", "  vim = https://vim.org", "  git = https://git-scm.com", "  jq = https://jqlang.org", "  note", "guide > tools"]);
+        assert!(stripped.lines.iter().all(|line| line.comment_text().is_none()));
+    }
+
+    #[test]
+    fn references_are_counted_per_domain() {
+        let counts = root("+ docs\n  a = b\n+ tools\n  guide > docs\n  manual > docs\n  old > gone\n+ tools\n+ empty").reference_counts();
+
+        assert_eq!(counts.counts, HashMap::from([("docs".to_string(), 2), ("tools".to_string(), 0), ("empty".to_string(), 0)]));
+        assert_eq!(counts.ambiguous, vec!["tools".to_string()]);
+    }
+}
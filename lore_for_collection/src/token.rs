@@ -0,0 +1,180 @@
+use std::ops::Range;
+
+// 词法单元的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Indent, // 行首的缩进
+    PinMarker, // 置顶的 `*` 和它后面的空白
+    DomainMarker, // domain 的 `+` 和它后面的空白
+    Key, // link 的键
+    Separator, // link 的 `=` 和它两边的空白
+    Value, // link 的值
+    Arrow, // 引用的 `>` 和它两边的空白
+    Text, // atom 的文本、domain 的名字，或者引用的名字和目标
+    CommentMarker, // 行尾注释的 ` # ` 和它两边的空白
+    Comment, // 行尾注释的文字
+}
+
+// 一个词法单元，range 是它在行中的字节范围
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub range: Range<usize>,
+}
+
+// 把一行切分成带类型的片段，所有片段拼起来正好是原来的行，规则与 parse_line 一致
+pub fn tokenize(line: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut push = |kind, range: Range<usize>| {
+        if !range.is_empty() {
+            tokens.push(Token { kind, range });
+        }
+    };
+
+    // 缩进
+    let indent = line.len() - line.trim_start().len();
+    push(TokenKind::Indent, 0..indent);
+
+    // 后面是 link 或 domain 时，`* ` 是置顶标记
+    let mut start = indent;
+    if let Some(rest) = line[indent..].strip_prefix("* ") {
+        let rest = rest.trim_start();
+        if (rest.starts_with('+') && rest.len() > 1) || rest.contains('=') {
+            start = line.len() - rest.len();
+            push(TokenKind::PinMarker, indent..start);
+        }
+    }
+
+    let trimmed = &line[start..];
+
+    if trimmed.starts_with('+') && trimmed.len() > 1 {
+        // `+` 和后面的空白都属于标记
+        let name = start + 1 + (trimmed[1..].len() - trimmed[1..].trim_start().len());
+        push(TokenKind::DomainMarker, start..name);
+        push(TokenKind::Text, name..line.len());
+    } else if let Some(pos) = trimmed.find('=') {
+        let eq = start + pos;
+
+        // 键后面和值前面的空白算在分隔符里
+        let key_end = start + line[start..eq].trim_end().len();
+        let value_start = line.len() - line[eq + 1..].trim_start().len();
+
+        push(TokenKind::Key, start..key_end);
+        push(TokenKind::Separator, key_end..value_start);
+        push(TokenKind::Value, value_start..line.len());
+    } else if let Some(pos) = trimmed.find('>').filter(|&pos| !trimmed[..pos].trim().is_empty() && !trimmed[pos + 1..].trim().is_empty()) {
+        let arrow = start + pos;
+
+        // 名字后面和目标前面的空白算在 `>` 里
+        let name_end = start + line[start..arrow].trim_end().len();
+        let target_start = line.len() - line[arrow + 1..].trim_start().len();
+
+        push(TokenKind::Text, start..name_end);
+        push(TokenKind::Arrow, name_end..target_start);
+        push(TokenKind::Text, target_start..line.len());
+    } else {
+        push(TokenKind::Text, start..line.len());
+    }
+
+    tokens
+}
+
+// 与打开 trailing_comments 时的默认解析一致：link 和 atom 的行尾注释单独成为片段
+pub fn tokenize_with_comments(line: &str) -> Vec<Token> {
+    let (code, comment) = crate::parser::split_comment(line);
+    let mut tokens = tokenize(code);
+
+    // 行尾注释只用于 link 和 atom
+    if comment.is_none() || tokens.iter().any(|token| matches!(token.kind, TokenKind::DomainMarker | TokenKind::Arrow)) {
+        return tokenize(line);
+    }
+
+    // code 后面只有空白，第一个 `#` 就是注释的标记
+    let hash = code.len() + line[code.len()..].find('#').unwrap();
+    let text = line.len() - line[hash + 1..].trim_start().len();
+
+    tokens.push(Token { kind: TokenKind::CommentMarker, range: code.len()..text });
+    if text < line.len() {
+        tokens.push(Token { kind: TokenKind::Comment, range: text..line.len() });
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(line: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(line).into_iter().map(|token| (token.kind, &line[token.range])).collect()
+    }
+
+    #[test]
+    fn link_line_spans() {
+        assert_eq!(
+            spans("  bai_du  =  https://www.baidu.com"),
+            vec![
+                (TokenKind::Indent, "  "),
+                (TokenKind::Key, "bai_du"),
+                (TokenKind::Separator, "  =  "),
+                (TokenKind::Value, "https://www.baidu.com"),
+            ]
+        );
+        assert_eq!(tokenize("  a = b")[1].range, 2..3);
+    }
+
+    #[test]
+    fn domain_line_spans() {
+        assert_eq!(
+            spans("* +  favourites"),
+            vec![
+                (TokenKind::PinMarker, "* "),
+                (TokenKind::DomainMarker, "+  "),
+                (TokenKind::Text, "favourites"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reference_and_atom_spans() {
+        assert_eq!(spans("guide > docs"), vec![(TokenKind::Text, "guide"), (TokenKind::Arrow, " > "), (TokenKind::Text, "docs")]);
+        assert_eq!(spans("* just text"), vec![(TokenKind::Text, "* just text")]);
+    }
+
+    #[test]
+    fn tokens_join_back_into_the_line() {
+        for line in ["", "    ", "+ a", "  x=y", "a > b = c", "* + p", "中文 = 链接"] {
+            let joined: String = tokenize(line).into_iter().map(|token| &line[token.range]).collect();
+            assert_eq!(joined, line);
+        }
+    }
+
+    #[test]
+    fn trailing_comment_spans() {
+        let line = "  vim = https://x  #  my editor";
+        let spans: Vec<_> = tokenize_with_comments(line).into_iter().map(|token| (token.kind, &line[token.range])).collect();
+        assert_eq!(
+            spans,
+            vec![
+                (TokenKind::Indent, "  "),
+                (TokenKind::Key, "vim"),
+                (TokenKind::Separator, " = "),
+                (TokenKind::Value, "https://x"),
+                (TokenKind::CommentMarker, "  #  "),
+                (TokenKind::Comment, "my editor"),
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_are_only_split_where_the_parser_splits_them() {
+        for line in ["+ tools # later", "a > b # c", "  # TODO: fix", "docs = https://x/#frag", "a \\# b"] {
+            assert_eq!(tokenize_with_comments(line), tokenize(line));
+        }
+
+        let line = "note # x ";
+        let tokens = tokenize_with_comments(line);
+        assert_eq!(tokens[0], Token { kind: TokenKind::Text, range: 0..4 });
+        assert_eq!(tokens.into_iter().map(|token| &line[token.range]).collect::<String>(), line);
+    }
+}
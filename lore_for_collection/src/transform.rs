@@ -0,0 +1,248 @@
+use std::collections::{HashMap, HashSet};
+use crate::line::{Content, Line, INDENT_UNIT};
+use crate::parser::{IndentModel, SpaceIndent};
+use crate::root::Root;
+use crate::tree;
+
+// 去重的范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupScope {
+    Global, // 整个文档内去重
+    PerDomain, // 只在同一个 domain 内去重
+}
+
+// 去掉重复的 link（名字和链接都相同），保留第一次出现的位置，其它行不受影响
+pub fn dedup_links(lines: Vec<Line>, scope: DedupScope) -> Vec<Line> {
+    let parents = tree::parents(&lines);
+
+    // 根据范围决定每个 link 属于哪个 domain
+    let domains: Vec<Option<usize>> = (0..lines.len())
+        .map(|index| match scope {
+            DedupScope::Global => None,
+            DedupScope::PerDomain => tree::enclosing_domain_index(&lines, &parents, index),
+        })
+        .collect();
+
+    let mut seen: HashSet<(Option<usize>, String, String)> = HashSet::new();
+
+    lines
+        .into_iter()
+        .zip(domains)
+        .filter(|(line, domain)| match &line.content {
+            Content::Link(key, value) => seen.insert((*domain, key.clone(), value.clone())),
+            _ => true,
+        })
+        .map(|(line, _)| line)
+        .collect()
+}
+
+// 按顶层节点把文档拆成多个子文档，按文档顺序排列，每个子文档包含一个顶层节点和它的所有后代，
+// 不在 domain 里的顶层 atom 和 link 单独成为一个子文档，元数据复制到每个子文档中
+pub fn split_by_root(root: Root) -> Vec<Root> {
+    let parents = tree::parents(&root.lines);
+    let mut parts: Vec<Root> = Vec::new();
+
+    for (line, parent) in root.lines.into_iter().zip(parents) {
+        match (parent, parts.last_mut()) {
+            (Some(_), Some(part)) => part.lines.push(line),
+            _ => parts.push(Root {
+                metadata: root.metadata.clone(),
+                lines: vec![line],
+            }),
+        }
+    }
+
+    parts
+}
+
+// 按解析器理解的层级重写每一行的缩进，每级两个空格，空行保留但清空其中的空白
+//
+// 奇数的缩进按解析器的方式向下取整，比父节点深出一级以上的行收回到父节点加一
+pub fn autofix_indent(input: &str) -> String {
+    autofix_indent_with(input, &SpaceIndent(INDENT_UNIT))
+}
+
+// 按指定的缩进写法理解层级，输出的缩进仍然是每级两个空格
+pub fn autofix_indent_with(input: &str, model: &impl IndentModel) -> String {
+    // 当前路径上各行的缩进
+    let mut stack: Vec<usize> = Vec::new();
+
+    input
+        .split('\n')
+        .map(|raw| {
+            let text = model.strip(raw).trim();
+            if text.is_empty() {
+                return String::new();
+            }
+
+            let indent = model.indent_of(raw);
+            while stack.last().is_some_and(|&top| top >= indent) {
+                stack.pop();
+            }
+            let depth = stack.len();
+            stack.push(indent);
+
+            format!("{}{}", " ".repeat(depth * INDENT_UNIT), text)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// 把同名的顶层 domain 合并到第一次出现的位置，后面的 domain 的子节点依次接在前面的子节点之后
+//
+// 合并后行的顺序不再是源文件的顺序，line_no 也不再递增，按行号查找时不能假定它是有序的
+pub fn merge_same_name_domains(lines: Vec<Line>) -> Vec<Line> {
+    let parents = tree::parents(&lines);
+
+    // 每个顶层节点和它的后代
+    let mut blocks: Vec<Vec<Line>> = Vec::new();
+
+    // domain 的名字在 blocks 中第一次出现的位置
+    let mut first: HashMap<String, usize> = HashMap::new();
+
+    for (line, parent) in lines.into_iter().zip(parents) {
+        if parent.is_some() {
+            blocks.last_mut().unwrap().push(line);
+            continue;
+        }
+
+        if let Content::Domain(name) = &line.content
+            && !first.contains_key(name)
+        {
+            first.insert(name.clone(), blocks.len());
+        }
+        blocks.push(vec![line]);
+    }
+
+    // 依次把重复的 domain 并入第一次出现的块，并入之后原来的位置留空
+    let mut merged: Vec<Option<Vec<Line>>> = blocks.into_iter().map(Some).collect();
+    for index in 0..merged.len() {
+        let target = match &merged[index].as_ref().unwrap()[0].content {
+            Content::Domain(name) => first[name],
+            _ => continue,
+        };
+        if target == index {
+            continue;
+        }
+
+        let mut block = merged[index].take().unwrap().into_iter();
+        let head = block.next().unwrap();
+        let into = merged[target].as_mut().unwrap();
+        let indent = into[0].indent;
+
+        // 子节点的缩进跟随合并后的 domain
+        into.extend(block.map(|line| Line {
+            indent: (line.indent + indent).saturating_sub(head.indent),
+            ..line
+        }));
+    }
+
+    merged.into_iter().flatten().flatten().collect()
+}
+
+// 取出第一个名为 name 的 domain 和它的子树，作为单独的文档，缩进从 0 开始，元数据保持不变
+pub fn extract_domain(root: Root, name: &str) -> Option<Root> {
+    let index = root
+        .lines
+        .iter()
+        .position(|line| matches!(&line.content, Content::Domain(domain) if domain == name))?;
+    let end = tree::subtree_end(&root.lines, index);
+    let indent = root.lines[index].indent;
+
+    let lines = root
+        .lines
+        .into_iter()
+        .skip(index)
+        .take(end - index)
+        .map(|line| Line {
+            indent: line.indent - indent,
+            ..line
+        })
+        .collect();
+
+    Some(Root {
+        metadata: root.metadata,
+        lines,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_lore::{parse_root, parse_with_options};
+    use crate::parser::ParseOptions;
+
+    fn parse(input: &str) -> Vec<Line> {
+        parse_with_options(input, &ParseOptions::default())
+    }
+
+    fn texts(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    const DUPLICATED: &str = "+ a\n  x = 1\n  note\n  x = 1\n+ b\n  x = 1\n  y = 2";
+
+    #[test]
+    fn global_dedup_keeps_only_the_first_link() {
+        assert_eq!(
+            texts(&dedup_links(parse(DUPLICATED), DedupScope::Global)),
+            vec!["+ a", "  x = 1", "  note", "+ b", "  y = 2"]
+        );
+    }
+
+    #[test]
+    fn per_domain_dedup_keeps_one_link_in_each_domain() {
+        assert_eq!(
+            texts(&dedup_links(parse(DUPLICATED), DedupScope::PerDomain)),
+            vec!["+ a", "  x = 1", "  note", "+ b", "  x = 1", "  y = 2"]
+        );
+    }
+
+    #[test]
+    fn same_name_with_another_value_is_kept() {
+        assert_eq!(texts(&dedup_links(parse("x = 1\nx = 2"), DedupScope::Global)), vec!["x = 1", "x = 2"]);
+    }
+
+    #[test]
+    fn split_gives_one_document_per_top_level_node() {
+        let root = parse_root("---\ntitle: all\n---\n+ tools\n  vim = v\n  + nested\n    x\nloose = l\n+ music\n  radio", &ParseOptions::default());
+        let parts = split_by_root(root);
+
+        assert_eq!(parts.len(), 3);
+        assert_eq!(texts(&parts[0].lines), vec!["+ tools", "  vim = v", "  + nested", "    x"]);
+        assert_eq!(texts(&parts[1].lines), vec!["loose = l"]);
+        assert_eq!(texts(&parts[2].lines), vec!["+ music", "  radio"]);
+        assert!(parts.iter().all(|part| part.title() == Some("all")));
+    }
+
+    #[test]
+    fn autofix_snaps_messy_indentation_to_levels() {
+        let messy = "+ tools\n      vim = https://x\n   + more\n          deep\n\n \nlast";
+        assert_eq!(autofix_indent(messy), "+ tools\n  vim = https://x\n  + more\n    deep\n\n\nlast");
+    }
+
+    #[test]
+    fn autofix_keeps_clean_input() {
+        let clean = "+ a\n  b = c\n  + d\n    e\nf";
+        assert_eq!(autofix_indent(clean), clean);
+        assert_eq!(autofix_indent(&autofix_indent("a\n     b\n c")), autofix_indent("a\n     b\n c"));
+    }
+
+    #[test]
+    fn same_name_domains_merge_in_occurrence_order() {
+        let lines = merge_same_name_domains(parse("+ tools\n  vim = https://vim.org\n+ news\n  hn = https://news.ycombinator.com\n+ tools\n  git = https://git-scm.com\n  + more\n    jq = https://jqlang.org"));
+
+        assert_eq!(texts(&lines), vec!["+ tools", "  vim = https://vim.org", "  git = https://git-scm.com", "  + more", "    jq = https://jqlang.org", "+ news", "  hn = https://news.ycombinator.com"]);
+        assert_eq!(lines.iter().map(|line| line.line_no).collect::<Vec<_>>(), vec![1, 2, 6, 7, 8, 3, 4]);
+    }
+
+    #[test]
+    fn extracted_domain_starts_at_indent_zero() {
+        let root = parse_root("+ tools\n  + editors\n    vim = https://vim.org\n    + plugins\n      fzf\n  git = https://git-scm.com\n+ editors\n  copy", &ParseOptions::default());
+        let extracted = extract_domain(root, "editors").unwrap();
+
+        assert_eq!(texts(&extracted.lines), vec!["+ editors", "  vim = https://vim.org", "  + plugins", "    fzf"]);
+        assert_eq!(extracted.lines.iter().map(|line| line.indent).collect::<Vec<_>>(), vec![0, 1, 1, 2]);
+        assert!(extract_domain(parse_root("+ a", &ParseOptions::default()), "missing").is_none());
+    }
+}
@@ -0,0 +1,818 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::fmt;
+use crate::line::{Content, Line};
+
+// 测试中记录每个线程算过几次高度，用来确认缓存生效
+#[cfg(test)]
+thread_local! {
+    static HEIGHT_PASSES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+// 每一行的父节点位置，父节点是之前最近的一个缩进更浅的行
+pub fn parents(lines: &[Line]) -> Vec<Option<usize>> {
+    let mut stack: Vec<usize> = Vec::new();
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| {
+            // 弹出缩进不浅于当前行的节点
+            while stack.last().is_some_and(|&top| lines[top].indent >= line.indent) {
+                stack.pop();
+            }
+
+            let parent = stack.last().copied();
+            stack.push(index);
+            parent
+        })
+        .collect()
+}
+
+// 所有祖先节点的位置，从最外层到最近的父节点
+pub fn ancestor_indices(lines: &[Line], index: usize) -> Vec<usize> {
+    let mut ancestors = Vec::new();
+    let mut indent = lines[index].indent;
+
+    // 向前找，每遇到一个更浅的行就是上一级祖先
+    for (position, line) in lines[..index].iter().enumerate().rev() {
+        if line.indent < indent {
+            ancestors.push(position);
+            indent = line.indent;
+        }
+    }
+
+    ancestors.reverse();
+    ancestors
+}
+
+// 所有祖先节点，从最外层到最近的父节点
+pub fn ancestors(lines: &[Line], index: usize) -> Vec<&Line> {
+    ancestor_indices(lines, index)
+        .into_iter()
+        .map(|position| &lines[position])
+        .collect()
+}
+
+// 最近的一个作为祖先的 domain，顶层节点没有
+pub fn enclosing_domain(lines: &[Line], index: usize) -> Option<&Line> {
+    ancestors(lines, index)
+        .into_iter()
+        .rev()
+        .find(|line| matches!(line.content, Content::Domain(_)))
+}
+
+// 两个节点最近的公共祖先，一个节点是另一个的祖先时返回它本身，
+// 两个不同的顶层节点没有公共祖先
+pub fn common_ancestor(lines: &[Line], a: usize, b: usize) -> Option<&Line> {
+    // 从最外层到节点本身的路径
+    let path = |index: usize| {
+        let mut path = ancestor_indices(lines, index);
+        path.push(index);
+        path
+    };
+
+    path(a)
+        .into_iter()
+        .zip(path(b))
+        .take_while(|(x, y)| x == y)
+        .last()
+        .map(|(index, _)| &lines[index])
+}
+
+// 沿着父节点找到最近的 domain
+pub(crate) fn enclosing_domain_index(lines: &[Line], parents: &[Option<usize>], index: usize) -> Option<usize> {
+    let mut current = parents[index];
+
+    while let Some(parent) = current {
+        if let Content::Domain(_) = lines[parent].content {
+            return Some(parent);
+        }
+        current = parents[parent];
+    }
+
+    None
+}
+
+// 查找选项
+#[derive(Default)]
+pub struct FindOptions {
+    pub case_insensitive: bool, // 比较名字时忽略大小写，对中日韩文字没有影响
+}
+
+// 按 domain 名字组成的路径查找节点，路径的每一级都必须是上一级的直接子 domain
+pub fn find_path(lines: &[Line], path: &[&str]) -> Option<usize> {
+    find_path_with(lines, path, &FindOptions::default())
+}
+
+// 按照查找选项查找路径
+pub fn find_path_with(lines: &[Line], path: &[&str], options: &FindOptions) -> Option<usize> {
+    let parents = parents(lines);
+    let same = |domain: &str, name: &str| match options.case_insensitive {
+        true => domain.to_lowercase() == name.to_lowercase(),
+        false => domain == name,
+    };
+
+    let mut current: Option<usize> = None;
+
+    for name in path {
+        current = Some(
+            lines
+                .iter()
+                .enumerate()
+                .position(|(index, line)| {
+                    parents[index] == current
+                        && matches!(&line.content, Content::Domain(domain) if same(domain, name))
+                })?,
+        );
+    }
+
+    current
+}
+
+// 子树的结束位置（不含），子树由 index 行和其后所有缩进更深的行组成
+pub fn subtree_end(lines: &[Line], index: usize) -> usize {
+    let indent = lines[index].indent;
+
+    lines[index + 1..]
+        .iter()
+        .position(|line| line.indent <= indent)
+        .map_or(lines.len(), |offset| index + 1 + offset)
+}
+
+// 整体平移一棵子树的缩进，返回实际平移的量
+//
+// 平移量会被限制：子树的根不能小于 0，也不能比前一行深出一级以上，
+// 子树内部的相对缩进保持不变，所以子节点不会变得比父节点更浅
+pub fn reindent_subtree(lines: &mut [Line], index: usize, delta: isize) -> isize {
+    let indent = lines[index].indent as isize;
+
+    // 子树的根最多只能成为前一行的子节点
+    let max_indent = match index {
+        0 => 0,
+        _ => lines[index - 1].indent as isize + 1,
+    };
+
+    // 限制后的目标缩进
+    let target = (indent + delta).min(max_indent.max(indent)).max(0);
+    let applied = target - indent;
+
+    let end = subtree_end(lines, index);
+    for line in &mut lines[index..end] {
+        line.indent = (line.indent as isize + applied) as usize;
+    }
+
+    applied
+}
+
+// 树上的节点，父子和兄弟关系都用在 Tree 中的位置表示
+pub struct TreeNode {
+    pub line: Line,
+    parent: Option<usize>,
+    first_child: Option<usize>,
+    last_child: Option<usize>,
+    next_sibling: Option<usize>,
+    metadata: HashMap<String, String>, // 分析时附加在节点上的信息，不会输出到 lore 文本
+}
+
+impl TreeNode {
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    // 给节点附加一项信息，已有同名的项时覆盖
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.metadata.insert(key.into(), value.into());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(String::as_str)
+    }
+}
+
+// 存放在一个数组里的树，建树只需要遍历一次，不需要为每个节点单独分配
+pub struct Tree {
+    nodes: Vec<TreeNode>,
+    first_root: Option<usize>,
+    last_root: Option<usize>,
+    detached: usize, // 已经移除的节点数，它们仍然占着数组中的位置
+    depths: OnceLock<Vec<usize>>, // 第一次查询时算出所有节点的深度，结构改变时清空
+    heights: OnceLock<Vec<usize>>, // 同上，每个节点的高度
+}
+
+impl Tree {
+    // 按缩进把行组织成树，父节点是之前最近的一个缩进更浅的行
+    pub fn build(lines: Vec<Line>) -> Tree {
+        let mut tree = Tree {
+            nodes: Vec::with_capacity(lines.len()),
+            first_root: None,
+            last_root: None,
+            detached: 0,
+            depths: OnceLock::new(),
+            heights: OnceLock::new(),
+        };
+
+        // 当前路径上的节点
+        let mut stack: Vec<usize> = Vec::new();
+
+        for line in lines {
+            while stack.last().is_some_and(|&top| tree.nodes[top].line.indent >= line.indent) {
+                stack.pop();
+            }
+
+            let index = tree.push(stack.last().copied(), line);
+            stack.push(index);
+        }
+
+        tree
+    }
+
+    // 把节点挂到 parent 的最后一个子节点之后，没有 parent 时作为顶层节点
+    fn push(&mut self, parent: Option<usize>, line: Line) -> usize {
+        self.clear_cache();
+        let index = self.nodes.len();
+
+        self.nodes.push(TreeNode {
+            line,
+            parent,
+            first_child: None,
+            last_child: None,
+            next_sibling: None,
+            metadata: HashMap::new(),
+        });
+
+        // 前一个兄弟节点
+        let previous = match parent {
+            Some(parent) => self.nodes[parent].last_child.replace(index),
+            None => self.last_root.replace(index),
+        };
+
+        match previous {
+            Some(previous) => self.nodes[previous].next_sibling = Some(index),
+            None => match parent {
+                Some(parent) => self.nodes[parent].first_child = Some(index),
+                None => self.first_root = Some(index),
+            },
+        }
+
+        index
+    }
+
+    // 仍在树中的节点数
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.detached
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // 在 parent 的子节点最后加入一行，缩进由 parent 决定，返回新节点的位置
+    pub fn add_child(&mut self, parent: usize, mut line: Line) -> usize {
+        line.indent = self.nodes[parent].line.indent + 1;
+        self.push(Some(parent), line)
+    }
+
+    // 把一行插入为 parent 的第 position 个子节点，超出范围时加在最后
+    pub fn insert_child(&mut self, parent: usize, position: usize, line: Line) -> usize {
+        let index = self.add_child(parent, line);
+
+        // 先加在最后，再移动到 position 处
+        if position + 1 < self.children(parent).count() {
+            let previous = self.unlink_child(parent, self.children(parent).count() - 1);
+            debug_assert_eq!(previous, Some(index));
+
+            match position {
+                0 => {
+                    self.nodes[index].next_sibling = self.nodes[parent].first_child;
+                    self.nodes[parent].first_child = Some(index);
+                },
+                _ => {
+                    let before = self.children(parent).nth(position - 1).unwrap();
+                    self.nodes[index].next_sibling = self.nodes[before].next_sibling;
+                    self.nodes[before].next_sibling = Some(index);
+                },
+            }
+
+            // 摘下来的时候清空了父节点
+            self.nodes[index].parent = Some(parent);
+        }
+
+        index
+    }
+
+    // 移除 parent 的第 position 个子节点和它的整棵子树，返回被移除节点的位置
+    pub fn remove_child(&mut self, parent: usize, position: usize) -> Option<usize> {
+        let index = self.unlink_child(parent, position)?;
+
+        let mut stack = vec![index];
+        while let Some(current) = stack.pop() {
+            self.detached += 1;
+            stack.extend(self.children(current));
+        }
+
+        Some(index)
+    }
+
+    // 把第 position 个子节点从兄弟链中摘下来
+    fn unlink_child(&mut self, parent: usize, position: usize) -> Option<usize> {
+        let index = self.children(parent).nth(position)?;
+        self.clear_cache();
+        let next = self.nodes[index].next_sibling.take();
+
+        match position {
+            0 => self.nodes[parent].first_child = next,
+            _ => {
+                let before = self.children(parent).nth(position - 1).unwrap();
+                self.nodes[before].next_sibling = next;
+            },
+        }
+
+        if self.nodes[parent].last_child == Some(index) {
+            self.nodes[parent].last_child = match position {
+                0 => None,
+                _ => self.children(parent).nth(position - 1),
+            };
+        }
+
+        self.nodes[index].parent = None;
+        Some(index)
+    }
+
+    pub fn node(&self, index: usize) -> &TreeNode {
+        &self.nodes[index]
+    }
+
+    pub fn node_mut(&mut self, index: usize) -> &mut TreeNode {
+        &mut self.nodes[index]
+    }
+
+    // 顶层节点
+    pub fn roots(&self) -> Siblings<'_> {
+        Siblings {
+            tree: self,
+            next: self.first_root,
+        }
+    }
+
+    // 某个节点的直接子节点
+    pub fn children(&self, index: usize) -> Siblings<'_> {
+        Siblings {
+            tree: self,
+            next: self.nodes[index].first_child,
+        }
+    }
+
+    // 节点的深度，顶层节点为 0，第一次查询时算出所有节点的深度，之后直接读取
+    pub fn depth(&self, index: usize) -> usize {
+        self.depths.get_or_init(|| {
+            let mut depths = vec![0; self.nodes.len()];
+            for (index, depth) in self.walk() {
+                depths[index] = depth;
+            }
+            depths
+        })[index]
+    }
+
+    // 节点的高度，即到最深的后代的距离，没有子节点时为 0，同样只计算一次
+    pub fn height(&self, index: usize) -> usize {
+        self.heights.get_or_init(|| {
+            #[cfg(test)]
+            HEIGHT_PASSES.with(|passes| passes.set(passes.get() + 1));
+
+            let order: Vec<usize> = self.walk().map(|(index, _)| index).collect();
+
+            // 倒过来遍历先序序列，子节点总是在父节点之前算好
+            let mut heights = vec![0; self.nodes.len()];
+            for &index in order.iter().rev() {
+                heights[index] = self.children(index).map(|child| heights[child] + 1).max().unwrap_or(0);
+            }
+            heights
+        })[index]
+    }
+
+    // 树的结构改变之后，缓存的深度和高度都不再有效
+    fn clear_cache(&mut self) {
+        self.depths.take();
+        self.heights.take();
+    }
+
+    // 节点在它的兄弟节点中的位置，从 0 开始
+    pub fn sibling_index(&self, index: usize) -> usize {
+        self.siblings(index).position(|sibling| sibling == index).unwrap_or(0)
+    }
+
+    // 节点和它的兄弟节点一共有多少个，包括它自己
+    pub fn sibling_count(&self, index: usize) -> usize {
+        self.siblings(index).count()
+    }
+
+    // 与节点有同一个父节点的所有节点，顶层节点互为兄弟
+    fn siblings(&self, index: usize) -> Siblings<'_> {
+        match self.nodes[index].parent {
+            Some(parent) => self.children(parent),
+            None => self.roots(),
+        }
+    }
+
+    // 按文档顺序（先序）遍历所有节点，同时给出深度
+    pub fn walk(&self) -> Walk<'_> {
+        Walk {
+            tree: self,
+            stack: self.first_root.map(|root| (root, 0)).into_iter().collect(),
+        }
+    }
+
+    // 按文档顺序取回所有行
+    pub fn into_lines(self) -> Vec<Line> {
+        let order: Vec<usize> = self.walk().map(|(index, _)| index).collect();
+
+        let mut nodes: Vec<Option<TreeNode>> = self.nodes.into_iter().map(Some).collect();
+        order
+            .into_iter()
+            .filter_map(|index| nodes[index].take())
+            .map(|node| node.line)
+            .collect()
+    }
+}
+
+impl Tree {
+    // 画成 ascii 树，fold_chains 为 true 时把只有一个子 domain 的 domain 链画在同一行
+    pub fn render(&self, fold_chains: bool) -> String {
+        let mut out = String::new();
+
+        for root in self.roots() {
+            self.render_node(root, "", None, fold_chains, &mut out);
+        }
+
+        out
+    }
+
+    // last 为 None 表示顶层节点，不画连接线
+    fn render_node(&self, index: usize, prefix: &str, last: Option<bool>, fold_chains: bool, out: &mut String) {
+        let mut label = label(&self.nodes[index].line);
+        let mut current = index;
+
+        // 沿着只有一个子 domain 的链往下走
+        while fold_chains && matches!(self.nodes[current].line.content, Content::Domain(_)) {
+            let mut children = self.children(current);
+            match (children.next(), children.next()) {
+                (Some(child), None) => match &self.nodes[child].line.content {
+                    Content::Domain(name) => {
+                        label.push_str(&format!(" → {}", name));
+                        current = child;
+                    },
+                    _ => break,
+                },
+                _ => break,
+            }
+        }
+
+        // 连接线
+        let child_prefix = match last {
+            None => {
+                out.push_str(&label);
+                String::new()
+            },
+            Some(last) => {
+                out.push_str(&format!("{}{}{}", prefix, if last { "└── " } else { "├── " }, label));
+                format!("{}{}", prefix, if last { "    " } else { "│   " })
+            },
+        };
+        out.push('\n');
+
+        let children: Vec<usize> = self.children(current).collect();
+        for (position, &child) in children.iter().enumerate() {
+            self.render_node(child, &child_prefix, Some(position + 1 == children.len()), fold_chains, out);
+        }
+    }
+}
+
+impl fmt::Display for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(false))
+    }
+}
+
+// 节点在树中显示的文字
+fn label(line: &Line) -> String {
+    match &line.content {
+        Content::Atom(atom) => atom.clone(),
+        Content::Link(key, value) => format!("{} = {}", key, value),
+        Content::Domain(domain) => format!("+ {}", domain),
+        Content::Reference(name, target) => format!("{} > {}", name, target),
+        Content::Elided(count) => format!("… ({} more)", count),
+    }
+}
+
+// 兄弟节点的迭代器
+pub struct Siblings<'a> {
+    tree: &'a Tree,
+    next: Option<usize>,
+}
+
+impl Iterator for Siblings<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let current = self.next?;
+        self.next = self.tree.nodes[current].next_sibling;
+        Some(current)
+    }
+}
+
+// 先序遍历的迭代器
+pub struct Walk<'a> {
+    tree: &'a Tree,
+    stack: Vec<(usize, usize)>, // 待访问的节点和它的深度
+}
+
+impl Iterator for Walk<'_> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        let (index, depth) = self.stack.pop()?;
+        let node = &self.tree.nodes[index];
+
+        // 先压入兄弟节点，再压入第一个子节点，保证子节点先被访问
+        if let Some(sibling) = node.next_sibling {
+            self.stack.push((sibling, depth));
+        }
+        if let Some(child) = node.first_child {
+            self.stack.push((child, depth + 1));
+        }
+
+        Some((index, depth))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_lore::parse_with_options;
+    use crate::parser::ParseOptions;
+
+    fn parse(input: &str) -> Vec<Line> {
+        parse_with_options(input, &ParseOptions::default())
+    }
+
+    fn indents(lines: &[Line]) -> Vec<usize> {
+        lines.iter().map(|line| line.indent).collect()
+    }
+
+    #[test]
+    fn reindent_moves_the_whole_subtree() {
+        let mut lines = parse("+ a\n+ b\n  c\nd");
+
+        assert_eq!(reindent_subtree(&mut lines, 1, 1), 1);
+        assert_eq!(indents(&lines), vec![0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn reindent_clamps_at_zero() {
+        let mut lines = parse("+ a\n  + b\n    c");
+
+        assert_eq!(reindent_subtree(&mut lines, 1, -5), -1);
+        assert_eq!(indents(&lines), vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn reindent_cannot_go_deeper_than_a_child_of_the_previous_line() {
+        let mut lines = parse("+ a\n+ b\n  c");
+
+        // b 最多成为 a 的子节点，c 仍然是 b 的子节点
+        assert_eq!(reindent_subtree(&mut lines, 1, 3), 1);
+        assert_eq!(indents(&lines), vec![0, 1, 2]);
+        assert_eq!(parents(&lines), vec![None, Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn enclosing_domain_of_a_deeply_nested_link() {
+        let lines = parse("+ a\n  + b\n    note\n      x = 1\n+ c");
+
+        assert_eq!(enclosing_domain(&lines, 3).map(|line| &line.content), Some(&Content::Domain("b".to_string())));
+        assert_eq!(ancestor_indices(&lines, 3), vec![0, 1, 2]);
+        assert_eq!(ancestors(&lines, 3).len(), 3);
+    }
+
+    #[test]
+    fn top_level_nodes_have_no_enclosing_domain() {
+        let lines = parse("note\n+ a\n  x = 1");
+
+        assert!(enclosing_domain(&lines, 0).is_none());
+        assert!(ancestors(&lines, 0).is_empty());
+        assert!(enclosing_domain(&lines, 1).is_none());
+    }
+
+    #[test]
+    fn common_ancestor_of_siblings_is_their_parent() {
+        let lines = parse("+ a\n  x = 1\n  y = 2");
+
+        assert_eq!(common_ancestor(&lines, 1, 2).map(|line| line.line_no), Some(1));
+    }
+
+    #[test]
+    fn common_ancestor_of_a_node_and_its_descendant_is_the_node() {
+        let lines = parse("+ a\n  + b\n    x = 1");
+
+        assert_eq!(common_ancestor(&lines, 1, 2).map(|line| line.line_no), Some(2));
+        assert_eq!(common_ancestor(&lines, 2, 0).map(|line| line.line_no), Some(1));
+    }
+
+    #[test]
+    fn top_level_nodes_have_no_common_ancestor() {
+        let lines = parse("+ a\n  x = 1\n+ b\n  y = 2");
+
+        assert!(common_ancestor(&lines, 1, 3).is_none());
+        assert!(common_ancestor(&lines, 0, 2).is_none());
+    }
+
+    // 每个节点的深度，按父节点数组计算
+    fn depths(lines: &[Line]) -> Vec<usize> {
+        let parents = parents(lines);
+        let mut depths: Vec<usize> = Vec::with_capacity(lines.len());
+        for parent in &parents {
+            depths.push(parent.map_or(0, |parent| depths[parent] + 1));
+        }
+        depths
+    }
+
+    #[test]
+    fn tree_matches_the_flat_shape() {
+        let lines = parse("+ a\n  x = 1\n  + b\n    note\n  y = 2\nz\n+ c\n    deep\n  w = 3");
+        let expected_parents = parents(&lines);
+        let expected_depths = depths(&lines);
+        let rendered: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+
+        let tree = Tree::build(lines);
+        assert_eq!(tree.len(), rendered.len());
+
+        // 先序遍历就是文档顺序，位置就是行在文档中的位置
+        let walked: Vec<(usize, usize)> = tree.walk().collect();
+        assert_eq!(walked.iter().map(|&(index, _)| index).collect::<Vec<_>>(), (0..rendered.len()).collect::<Vec<_>>());
+        assert_eq!(walked.iter().map(|&(_, depth)| depth).collect::<Vec<_>>(), expected_depths);
+
+        for (index, parent) in expected_parents.iter().enumerate() {
+            assert_eq!(tree.node(index).parent(), *parent);
+        }
+
+        let lines: Vec<String> = tree.into_lines().iter().map(|line| line.to_string()).collect();
+        assert_eq!(lines, rendered);
+    }
+
+    #[test]
+    fn large_document_builds_in_one_pass() {
+        // 每个顶层 domain 下有三层嵌套
+        let mut input = String::new();
+        for i in 0..20_000 {
+            input.push_str(&format!("+ d{}\n  x{} = https://example.com/{}\n  + e\n    + f\n      leaf\n", i, i, i));
+        }
+        let lines = parse(&input);
+        let expected_depths = depths(&lines);
+
+        let tree = Tree::build(lines);
+
+        assert_eq!(tree.len(), 100_000);
+        assert_eq!(tree.roots().count(), 20_000);
+        assert_eq!(tree.walk().map(|(_, depth)| depth).collect::<Vec<_>>(), expected_depths);
+        assert_eq!(tree.height(0), 3);
+    }
+
+    #[test]
+    fn folding_a_straight_chain_puts_it_on_one_line() {
+        let tree = Tree::build(parse("+ a\n  + b\n    + c\n      x = 1\n      y = 2"));
+
+        assert_eq!(
+            tree.render(false),
+            "+ a\n└── + b\n    └── + c\n        ├── x = 1\n        └── y = 2\n"
+        );
+        assert_eq!(tree.render(true), "+ a → b → c\n├── x = 1\n└── y = 2\n");
+        assert_eq!(tree.to_string(), tree.render(false));
+    }
+
+    #[test]
+    fn folding_stops_where_the_tree_branches() {
+        let tree = Tree::build(parse("+ a\n  + b\n    + c\n    + d"));
+
+        assert_eq!(tree.render(true), "+ a → b\n├── + c\n└── + d\n");
+    }
+
+    fn rendered(tree: Tree) -> Vec<String> {
+        tree.into_lines().iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn added_children_take_their_indent_from_the_parent() {
+        let mut tree = Tree::build(parse("+ a\n  + b"));
+        assert_eq!(tree.depth(1), 1);
+
+        let child = tree.add_child(1, Line::new(0, Content::Atom("x".to_string())));
+
+        assert_eq!(tree.node(child).parent(), Some(1));
+        assert_eq!(tree.depth(child), 2);
+        assert_eq!(tree.height(0), 2);
+        assert_eq!(rendered(tree), vec!["+ a", "  + b", "    x"]);
+    }
+
+    #[test]
+    fn inserted_children_keep_their_parent() {
+        let mut tree = Tree::build(parse("+ a\n  x\n  y"));
+        assert_eq!(tree.depth(2), 1);
+
+        let first = tree.insert_child(0, 0, Line::new(5, Content::Atom("first".to_string())));
+        let middle = tree.insert_child(0, 2, Line::new(0, Content::Atom("middle".to_string())));
+        let last = tree.insert_child(0, 9, Line::new(0, Content::Atom("last".to_string())));
+
+        for (index, position) in [(first, 0), (middle, 2), (last, 4)] {
+            assert_eq!(tree.node(index).parent(), Some(0));
+            assert_eq!(tree.sibling_index(index), position);
+            assert_eq!(tree.sibling_count(index), 5);
+            assert_eq!(tree.depth(index), 1);
+        }
+        assert_eq!(rendered(tree), vec!["+ a", "  first", "  x", "  middle", "  y", "  last"]);
+    }
+
+    #[test]
+    fn removed_children_take_their_subtree_with_them() {
+        let mut tree = Tree::build(parse("+ a\n  + b\n    c\n  d\n+ e"));
+        assert_eq!(tree.height(0), 2);
+
+        assert_eq!(tree.remove_child(0, 0), Some(1));
+        assert_eq!(tree.remove_child(0, 5), None);
+
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.height(0), 1);
+        assert_eq!(tree.sibling_index(3), 0);
+        assert_eq!(tree.sibling_count(3), 1);
+        assert_eq!(rendered(tree), vec!["+ a", "  d", "+ e"]);
+    }
+
+    #[test]
+    fn children_know_their_position_among_siblings() {
+        let tree = Tree::build(parse("+ a\n  x = 1\n  + b\n    inner\n  y\n  z > a\n+ c"));
+        let children: Vec<usize> = tree.children(0).collect();
+
+        assert_eq!(children, vec![1, 2, 4, 5]);
+        for (position, &child) in children.iter().enumerate() {
+            assert_eq!(tree.sibling_index(child), position);
+            assert_eq!(tree.sibling_count(child), 4);
+        }
+
+        // 顶层节点互为兄弟
+        assert_eq!((tree.sibling_index(6), tree.sibling_count(6)), (1, 2));
+        assert_eq!((tree.sibling_index(3), tree.sibling_count(3)), (0, 1));
+    }
+
+    #[test]
+    fn nodes_carry_metadata_that_is_not_rendered() {
+        let mut tree = Tree::build(parse("+ links\n  old = https://old.example"));
+
+        tree.node_mut(1).set("status", "dead");
+        tree.node_mut(1).set("status", "404");
+        tree.node_mut(1).set("checked", "2024-05-01");
+
+        assert_eq!(tree.node(1).get("status"), Some("404"));
+        assert_eq!(tree.node(1).get("checked"), Some("2024-05-01"));
+        assert_eq!(tree.node(0).get("status"), None);
+        assert_eq!(rendered(tree), vec!["+ links", "  old = https://old.example"]);
+    }
+
+    #[test]
+    fn paths_match_case_insensitively_only_when_asked() {
+        let lines = parse("+ Tools\n  + Editors\n    vim = https://vim.org\n+ 中文");
+        let fold = FindOptions { case_insensitive: true };
+
+        assert_eq!(find_path(&lines, &["tools", "editors"]), None);
+        assert_eq!(find_path_with(&lines, &["tools", "editors"], &fold), Some(1));
+        assert_eq!(find_path_with(&lines, &["TOOLS", "Editors"], &fold), Some(1));
+        assert_eq!(find_path_with(&lines, &["中文"], &fold), Some(3));
+    }
+
+    #[test]
+    fn last_leaf_of_a_deep_document_keeps_its_parent() {
+        let tree = Tree::build(parse("+ a\n  + b\n    + c\n      + d\n        x = 1\n        last"));
+
+        assert_eq!(tree.node(5).parent(), Some(3));
+        assert_eq!(tree.children(3).collect::<Vec<_>>(), vec![4, 5]);
+        assert_eq!(tree.depth(5), 4);
+        assert_eq!(tree.into_lines().last().unwrap().to_string(), "        last");
+    }
+
+    #[test]
+    fn heights_are_computed_once_until_the_tree_changes() {
+        fn shared<T: Sync>(_: &T) {}
+
+        let mut tree = Tree::build(parse("+ a\n  + b\n    c\n+ d"));
+        shared(&tree);
+        let passes = || HEIGHT_PASSES.with(|passes| passes.get());
+        let before = passes();
+
+        assert_eq!(tree.height(0), 2);
+        assert_eq!(tree.height(3), 0);
+        assert_eq!(passes(), before + 1);
+
+        tree.add_child(3, parse("e").remove(0));
+        assert_eq!(tree.height(3), 1);
+        assert_eq!(passes(), before + 2);
+    }
+}
@@ -0,0 +1,434 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::error::{Diagnostic, RoundtripError, Severity};
+use crate::input_lore;
+use crate::json;
+use crate::line::{Content, Line};
+use crate::output;
+use crate::tree;
+use crate::parser::ParseOptions;
+use crate::root::Root;
+
+// 检查整个文档，返回按行号排序的所有诊断，包括下面各个 check_ 函数的结果
+pub fn validate(root: &Root) -> Vec<Diagnostic> {
+    let lines = &root.lines;
+    let mut diagnostics = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let next = lines.get(index + 1);
+
+        // 缩进比上一行深出一级以上
+        let max_indent = match index {
+            0 => 0,
+            _ => lines[index - 1].indent + 1,
+        };
+        if line.indent > max_indent {
+            diagnostics.push(Diagnostic {
+                line: line.line_no,
+                severity: Severity::Warning,
+                code: "over-indented",
+                message: format!("line is indented {} levels but at most {} is expected", line.indent, max_indent),
+            });
+        }
+
+        match &line.content {
+            // 没有子节点的 domain
+            Content::Domain(name) => {
+                if next.is_none_or(|next| next.indent <= line.indent) {
+                    diagnostics.push(Diagnostic {
+                        line: line.line_no,
+                        severity: Severity::Warning,
+                        code: "empty-domain",
+                        message: format!("domain `{}` has no children", name),
+                    });
+                }
+            },
+
+            // 键或值为空的 link
+            Content::Link(key, value) => {
+                if key.is_empty() {
+                    diagnostics.push(Diagnostic {
+                        line: line.line_no,
+                        severity: Severity::Error,
+                        code: "empty-link-name",
+                        message: "link has no name before `=`".to_string(),
+                    });
+                }
+                if value.is_empty() {
+                    diagnostics.push(Diagnostic {
+                        line: line.line_no,
+                        severity: Severity::Error,
+                        code: "empty-link-value",
+                        message: format!("link `{}` has no value after `=`", key),
+                    });
+                }
+            },
+
+            Content::Atom(_) | Content::Reference(..) | Content::Elided(_) => {}
+        }
+    }
+
+    // 目标不存在的引用
+    for reference in root.references().filter(|reference| !reference.resolved) {
+        diagnostics.push(Diagnostic {
+            line: reference.line,
+            severity: Severity::Error,
+            code: "dangling-reference",
+            message: format!("reference `{}` points to `{}`, which is not a domain in this document", reference.name, reference.target),
+        });
+    }
+
+    diagnostics.extend(check_anchor_collisions(lines));
+    diagnostics.extend(check_children_of_nonparents(lines));
+    diagnostics.extend(check_encoding(root));
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    diagnostics
+}
+
+// 找出生成锚点时会得到相同 id 的 link 名字和 domain，按行号排列
+//
+// 锚点 id 在整个页面中必须唯一，所以在整个文档范围内比较，
+// 每个冲突的行报告一次，指向第一个使用这个 id 的行；
+// domain 的锚点是开启面包屑时实际输出的，冲突的 domain 会被加上 `-2` 这样的序号
+pub fn check_anchor_collisions(lines: &[Line]) -> Vec<Diagnostic> {
+    // 每个 id 第一次出现时的行号
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut diagnostics = Vec::new();
+
+    // domain 的锚点和 output 中的生成方式一致：祖先 domain 的缩进和锚点，以及已经用过的锚点
+    let mut trail: Vec<(usize, String)> = Vec::new();
+    let mut used: HashSet<String> = HashSet::new();
+    let mut domains: HashMap<String, usize> = HashMap::new();
+
+    for line in lines {
+        while trail.last().is_some_and(|&(indent, _)| indent >= line.indent) {
+            trail.pop();
+        }
+
+        if let Content::Domain(name) = &line.content {
+            let anchor = output::domain_anchor(trail.last().map(|(_, id)| id.as_str()), name);
+            let id = output::unique_anchor(anchor.clone(), &mut used);
+
+            if id != anchor {
+                diagnostics.push(Diagnostic {
+                    line: line.line_no,
+                    severity: Severity::Warning,
+                    code: "anchor-collision",
+                    message: format!("domain `{}` has the same anchor `{}` as line {}, it becomes `{}`", name, anchor, domains[&anchor], id),
+                });
+            }
+
+            domains.insert(id.clone(), line.line_no);
+            trail.push((line.indent, id));
+            continue;
+        }
+
+        let Content::Link(key, _) = &line.content else {
+            continue;
+        };
+
+        let id = output::slug(key);
+        if id.is_empty() {
+            continue;
+        }
+
+        match seen.get(&id) {
+            Some(&first) => diagnostics.push(Diagnostic {
+                line: line.line_no,
+                severity: Severity::Warning,
+                code: "anchor-collision",
+                message: format!("link `{}` has the same anchor `{}` as line {}", key, id, first),
+            }),
+            None => {
+                seen.insert(id, line.line_no);
+            },
+        }
+    }
+
+    diagnostics
+}
+
+// 找出缩进在 link、atom 或引用下面的行，只有 domain 才能包含子节点，这通常是缩进写错了
+pub fn check_children_of_nonparents(lines: &[Line]) -> Vec<Diagnostic> {
+    tree::parents(lines)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, parent)| {
+            let parent = &lines[parent?];
+            match parent.content {
+                Content::Domain(_) | Content::Elided(_) => None,
+                _ => Some(Diagnostic {
+                    line: lines[index].line_no,
+                    severity: Severity::Warning,
+                    code: "child-of-non-parent",
+                    message: format!(
+                        "line is indented under the {} on line {}, which cannot have children",
+                        parent.content.kind_name(),
+                        parent.line_no
+                    ),
+                }),
+            }
+        })
+        .collect()
+}
+
+// 找出含有替换字符 U+FFFD 的行，这通常说明文件在某次转换编码时已经损坏
+pub fn check_encoding(root: &Root) -> Vec<Diagnostic> {
+    root.lines
+        .iter()
+        .filter(|line| line.to_string().contains('\u{fffd}'))
+        .map(|line| Diagnostic {
+            line: line.line_no,
+            severity: Severity::Warning,
+            code: "replacement-character",
+            message: "line contains U+FFFD, the file may have been decoded with the wrong encoding".to_string(),
+        })
+        .collect()
+}
+
+// 统计每种行首空白宽度出现的次数，空行不计，一个制表符算 tab_width 个空格，
+// 可以据此判断文件用的是几个空格的缩进
+pub fn indent_histogram(input: &str, tab_width: usize) -> BTreeMap<usize, usize> {
+    let mut histogram = BTreeMap::new();
+
+    for line in input.split('\n').filter(|line| !line.trim().is_empty()) {
+        let width = line
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .map(|c| match c {
+                '\t' => tab_width,
+                _ => 1,
+            })
+            .sum();
+
+        *histogram.entry(width).or_insert(0) += 1;
+    }
+
+    histogram
+}
+
+// 生成的 html 中会嵌套的标签
+const NESTED_TAGS: [&str; 11] = ["details", "summary", "div", "section", "ul", "li", "p", "nav", "main", "table", "tr"];
+
+// 检查生成的 html 中 details、div 等会嵌套的标签是否成对并且正确嵌套，其它标签不检查
+pub fn validate_html_nesting(html: &str) -> Result<(), String> {
+    let mut open: Vec<&str> = Vec::new();
+
+    for (position, _) in html.match_indices('<') {
+        let rest = &html[position + 1..];
+        let (closing, rest) = match rest.strip_prefix('/') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let name = &rest[..rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len())];
+
+        if !NESTED_TAGS.contains(&name) {
+            continue;
+        }
+
+        if !closing {
+            open.push(name);
+            continue;
+        }
+
+        match open.pop() {
+            Some(top) if top == name => {},
+            Some(top) => return Err(format!("</{}> at byte {} closes <{}>", name, position, top)),
+            None => return Err(format!("</{}> at byte {} has no opening tag", name, position)),
+        }
+    }
+
+    match open.last() {
+        Some(name) => Err(format!("<{}> is never closed", name)),
+        None => Ok(()),
+    }
+}
+
+// 以 json 数组的形式输出所有诊断，供 CI 使用
+pub fn validate_json(root: &Root) -> String {
+    let items: Vec<String> = validate(root)
+        .iter()
+        .map(|diagnostic| {
+            format!(
+                r#"{{"line":{},"severity":{},"code":{},"message":{}}}"#,
+                diagnostic.line,
+                json::quote(diagnostic.severity.name()),
+                json::quote(diagnostic.code),
+                json::quote(&diagnostic.message)
+            )
+        })
+        .collect();
+
+    format!("[{}]", items.join(","))
+}
+
+// 解析、输出、再解析，确认结构不变，否则报告第一个不一致的行
+pub fn assert_roundtrip(input: &str) -> Result<(), RoundtripError> {
+    assert_lines_roundtrip(&input_lore::parse_with_options(input, &ParseOptions::default()))
+}
+
+// 输出已有的行再按默认规则解析，确认结构不变，用于检查由其它方言、选项或变换得到的行
+pub fn assert_lines_roundtrip(first: &[Line]) -> Result<(), RoundtripError> {
+    let rendered: Vec<String> = first.iter().map(|line| line.to_string()).collect();
+    let second = input_lore::parse_with_options(&rendered.join("\n"), &ParseOptions::default());
+
+    for (index, line) in first.iter().enumerate() {
+        match second.get(index) {
+            Some(again) if again.indent == line.indent && again.content == line.content => {},
+            again => {
+                return Err(RoundtripError {
+                    line: line.line_no,
+                    expected: describe_line(line),
+                    found: again.map_or("nothing".to_string(), describe_line),
+                });
+            },
+        }
+    }
+
+    // 再解析后多出来的行
+    if let Some(extra) = second.get(first.len()) {
+        return Err(RoundtripError {
+            line: first.last().map_or(0, |line| line.line_no),
+            expected: "nothing".to_string(),
+            found: describe_line(extra),
+        });
+    }
+
+    Ok(())
+}
+
+// 报告中的一行，文字相同时靠种类区分
+fn describe_line(line: &Line) -> String {
+    format!("{} `{}`", line.content.kind_name(), line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_lore::parse_root;
+
+    fn root(input: &str) -> Root {
+        parse_root(input, &ParseOptions::default())
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().map(|diagnostic| diagnostic.code).collect()
+    }
+
+    #[test]
+    fn json_lists_dangling_reference_and_empty_domain() {
+        let json = validate_json(&root("+ empty\n+ docs\n  guide > missing"));
+
+        assert_eq!(
+            json,
+            concat!(
+                r#"[{"line":1,"severity":"warning","code":"empty-domain","message":"domain `empty` has no children"},"#,
+                r#"{"line":3,"severity":"error","code":"dangling-reference","message":"reference `guide` points to `missing`, which is not a domain in this document"}]"#,
+            )
+        );
+    }
+
+    #[test]
+    fn resolved_references_are_fine() {
+        assert!(validate(&root("+ docs\n  guide > docs")).is_empty());
+    }
+
+    #[test]
+    fn validate_includes_the_other_checks() {
+        let diagnostics = validate(&root("+ d\n  l = x\n    under\n  C++ = a\n  C  +  + = b\n  bad \u{fffd}"));
+
+        assert_eq!(codes(&diagnostics), vec!["child-of-non-parent", "anchor-collision", "replacement-character"]);
+        assert_eq!(diagnostics[0].line, 3);
+        assert_eq!(diagnostics[1].line, 5);
+        assert_eq!(diagnostics[2].line, 6);
+    }
+
+    #[test]
+    fn empty_json_for_a_healthy_document() {
+        assert_eq!(validate_json(&root("+ links\n  a = b")), "[]");
+    }
+
+    #[test]
+    fn well_formed_documents_roundtrip() {
+        assert_eq!(assert_roundtrip("+ links\n  a=b\n   odd\n  * + pinned\n    guide>links\n\ntext"), Ok(()));
+    }
+
+    #[test]
+    fn literal_plus_atoms_do_not_roundtrip() {
+        let options = ParseOptions {
+            no_domains: true,
+            ..ParseOptions::default()
+        };
+        let lines = input_lore::parse_with_options("a = b\n+ bullet\nc", &options);
+
+        assert_eq!(
+            assert_lines_roundtrip(&lines),
+            Err(RoundtripError {
+                line: 2,
+                expected: "atom `+ bullet`".to_string(),
+                found: "domain `+ bullet`".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn names_with_the_same_slug_collide() {
+        let root = root("+ langs\n  C++ = https://isocpp.org\n  Rust = https://rust-lang.org\n  C  +  + = https://cppreference.com");
+        let diagnostics = check_anchor_collisions(&root.lines);
+
+        assert_eq!(output::slug("C++"), output::slug("C  +  +"));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 4);
+        assert_eq!(diagnostics[0].code, "anchor-collision");
+        assert_eq!(diagnostics[0].message, "link `C  +  +` has the same anchor `c` as line 2");
+    }
+
+    #[test]
+    fn domains_with_the_same_anchor_collide() {
+        let root = root("+ a b\n+ a\n  + b\n+ t\n  + x\n+ t\n  + x");
+        let diagnostics = check_anchor_collisions(&root.lines);
+
+        // `+ a b` 和 `a` 下面的 `b` 不冲突，第二个 `t` 下面的 `x` 跟着父 domain 的锚点也不冲突
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 6);
+        assert_eq!(diagnostics[0].message, "domain `t` has the same anchor `t` as line 4, it becomes `t-2`");
+    }
+
+    #[test]
+    fn lines_under_a_link_are_reported() {
+        let root = root("+ links\n  a = https://a.example\n    stray\n  + fine\n    ok");
+        let diagnostics = check_children_of_nonparents(&root.lines);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 3);
+        assert_eq!(diagnostics[0].code, "child-of-non-parent");
+        assert_eq!(diagnostics[0].message, "line is indented under the link on line 2, which cannot have children");
+    }
+
+    #[test]
+    fn replacement_characters_are_flagged() {
+        let diagnostics = check_encoding(&root("+ tools\n  vim = https://vim.org\n  \u{fffd}\u{fffd}文档"));
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 3);
+        assert_eq!(diagnostics[0].code, "replacement-character");
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(check_encoding(&root("+ 中文\n  正常")).is_empty());
+    }
+
+    #[test]
+    fn histogram_counts_mixed_indent_widths() {
+        let histogram = indent_histogram("+ a\n  b\n    c\n\n    d\n\te\n  f", 4);
+
+        assert_eq!(histogram, BTreeMap::from([(0, 1), (2, 2), (4, 3)]));
+    }
+
+    #[test]
+    fn html_nesting_reports_the_first_problem() {
+        assert_eq!(validate_html_nesting("<details><summary>a</summary><p>b</p></details>"), Ok(()));
+        assert_eq!(validate_html_nesting("<details><p>b</details>"), Err("</details> at byte 13 closes <p>".to_string()));
+        assert_eq!(validate_html_nesting("<div></div></div>"), Err("</div> at byte 11 has no opening tag".to_string()));
+        assert_eq!(validate_html_nesting("<ul><li>a</li>"), Err("<ul> is never closed".to_string()));
+        assert_eq!(validate_html_nesting("<a><strong>x</a>"), Ok(()));
+    }
+}